@@ -21,7 +21,7 @@ pub fn display_error(err: &CliError, shell: &mut Shell) {
 
 fn _display_error(err: &CliError, shell: &mut Shell, as_err: bool) -> bool {
     if as_err {
-        drop(shell.error(&err));
+        drop(shell.error(err));
     } else {
         drop(writeln!(shell.err(), "{}", err));
     }
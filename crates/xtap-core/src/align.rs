@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+use calamine::DataType;
+use xtap_util::{closest_msg, lev_distance};
+
+use crate::Record;
+
+/// The output columns when aligning columns by header names.
+///
+/// If a name appears multiple times in a header, each occurrence is treated as a separate column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schema {
+    /// All columns found in any source, in the order they first appear.
+    Union,
+    /// Only the columns found in every source, in the order of the first source.
+    Intersection,
+    /// The columns of the first source.
+    First,
+    /// The given columns, in the given order.
+    Explicit(Vec<String>),
+}
+
+/// The normalization applied to header names before comparing them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Normalize {
+    /// Compares header names exactly.
+    #[default]
+    None,
+    /// Ignores leading and trailing whitespace, and treats any run of whitespace as a single
+    /// space, e.g. ` Order  No` is the same as `Order No`.
+    Whitespace,
+    /// Like `Whitespace`, but also ignores case, e.g. `order no` is the same as `Order No`.
+    Case,
+    /// Ignores case and any character that is not alphanumeric, e.g. `order_no`, `Order No.` and
+    /// `OrderNo` are the same.
+    Alphanumeric,
+}
+
+impl Normalize {
+    /// Returns the normalized form of `name`.
+    fn apply(&self, name: &str) -> String {
+        match self {
+            Normalize::None => name.to_string(),
+            Normalize::Whitespace => {
+                name.split_whitespace().collect::<Vec<_>>().join(" ")
+            }
+            Normalize::Case => {
+                Normalize::Whitespace.apply(name).to_lowercase()
+            }
+            Normalize::Alphanumeric => name
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect(),
+        }
+    }
+}
+
+/// The rules of matching header names.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Matcher {
+    pub(crate) normalize: Normalize,
+    /// The maximum Levenshtein distance between the normalized names of the same column, where
+    /// `0` disables fuzzy matching.
+    pub(crate) distance: usize,
+    /// The canonical names of known synonyms, both of which are normalized once the matcher is
+    /// [`built`](Matcher::build).
+    pub(crate) aliases: HashMap<String, String>,
+}
+
+impl Matcher {
+    /// Adds a synonym `alias` of the header name `name`.
+    pub(crate) fn alias(&mut self, alias: &str, name: &str) {
+        self.aliases.insert(alias.to_string(), name.to_string());
+    }
+
+    /// Returns a copy of this matcher with its aliases normalized.
+    pub(crate) fn build(&self) -> Matcher {
+        let aliases = self
+            .aliases
+            .iter()
+            .map(|(alias, name)| {
+                (self.normalize.apply(alias), self.normalize.apply(name))
+            })
+            .collect();
+        Matcher { aliases, ..self.clone() }
+    }
+
+    /// Returns the columns of a header with the given names.
+    fn columns<I: IntoIterator<Item = String>>(
+        &self,
+        names: I,
+    ) -> Vec<Column> {
+        let mut columns: Vec<Column> = Vec::new();
+        for name in names {
+            let mut key = self.normalize.apply(&name);
+            if let Some(canonical) = self.aliases.get(&key) {
+                key = canonical.clone();
+            }
+            let nth = columns.iter().filter(|c| c.key == key).count();
+            columns.push(Column { name, key, nth });
+        }
+        columns
+    }
+
+    /// Returns the columns of the given header record.
+    fn header_columns(&self, headers: &Record) -> Vec<Column> {
+        self.columns(headers.iter().map(|field| field.to_display_string()))
+    }
+
+    /// Returns the index of the column in `columns` that each of the `found` columns of the part
+    /// described by `part` matches, if any.
+    ///
+    /// Exact matches of normalized names take precedence over fuzzy matches. A fuzzy match must be
+    /// unique in both directions, and a near miss within twice the maximum distance is reported as
+    /// an error rather than treated as a different column.
+    fn matches(
+        &self,
+        found: &[Column],
+        columns: &[Column],
+        part: &str,
+    ) -> Result<Vec<Option<usize>>> {
+        let mut matched = vec![None; found.len()];
+        let mut taken = vec![false; columns.len()];
+        for (i, column) in found.iter().enumerate() {
+            let exact = columns
+                .iter()
+                .position(|c| c.key == column.key && c.nth == column.nth);
+            if let Some(j) = exact.filter(|&j| !taken[j]) {
+                matched[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+        if self.distance == 0 {
+            return Ok(matched);
+        }
+
+        let unmatched: Vec<usize> =
+            (0..found.len()).filter(|&i| matched[i].is_none()).collect();
+        let mut free: Vec<usize> =
+            (0..columns.len()).filter(|&j| !taken[j]).collect();
+        let distance =
+            |i: usize, j: usize| lev_distance(&found[i].key, &columns[j].key);
+
+        for &i in unmatched.iter() {
+            let best = match free.iter().map(|&j| distance(i, j)).min() {
+                Some(best) => best,
+                None => break,
+            };
+            if best > self.distance {
+                // a near miss within twice the maximum distance is likely the same column.
+                let names = free
+                    .iter()
+                    .filter(|&&j| distance(i, j) <= 2 * self.distance)
+                    .map(|&j| columns[j].name.as_str());
+                let hint = closest_msg(&found[i].name, names, |name| name);
+                if !hint.is_empty() {
+                    return Err(invalid_data(format!(
+                        "the header `{}` of {} doesn't match any column closely enough{}",
+                        found[i].name, part, hint
+                    )));
+                }
+                continue;
+            }
+
+            let candidates: Vec<usize> = free
+                .iter()
+                .copied()
+                .filter(|&j| distance(i, j) == best)
+                .collect();
+            if candidates.len() > 1 {
+                let names: Vec<_> = candidates
+                    .iter()
+                    .map(|&j| format!("`{}`", columns[j].name))
+                    .collect();
+                return Err(invalid_data(format!(
+                    "the header `{}` of {} ambiguously matches the columns {}",
+                    found[i].name,
+                    part,
+                    names.join(", ")
+                )));
+            }
+
+            let j = candidates[0];
+            let rival = unmatched.iter().find(|&&k| {
+                k != i && matched[k].is_none() && distance(k, j) <= best
+            });
+            if let Some(&k) = rival {
+                return Err(invalid_data(format!(
+                    "the headers `{}` and `{}` of {} ambiguously match the column `{}`",
+                    found[i].name, found[k].name, part, columns[j].name
+                )));
+            }
+            matched[i] = Some(j);
+            free.retain(|&other| other != j);
+        }
+        Ok(matched)
+    }
+}
+
+/// A column identified by its header name and the occurrence of that name in the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    /// The original header name.
+    name: String,
+    /// The normalized header name.
+    key: String,
+    /// The number of preceding columns with the same normalized name in the header.
+    nth: usize,
+}
+
+/// Rearranges the fields of records in the order of the output columns.
+#[derive(Debug)]
+pub(crate) struct Alignment {
+    schema: Schema,
+    matcher: Matcher,
+    /// The output columns, which are unknown until the first header is read for `Schema::First`.
+    columns: Option<Vec<Column>>,
+    /// The index of the field of each output column in the current part, if any.
+    indexes: Vec<Option<usize>>,
+    /// A copy of the record being aligned.
+    buf: Record,
+}
+
+impl Alignment {
+    pub(crate) fn new(schema: Schema, matcher: Matcher) -> Alignment {
+        let columns = match schema {
+            Schema::Explicit(ref names) => {
+                Some(matcher.columns(names.iter().cloned()))
+            }
+            _ => None,
+        };
+        Alignment {
+            schema,
+            matcher,
+            columns,
+            indexes: Vec::new(),
+            buf: Record::default(),
+        }
+    }
+
+    /// Returns `true` if the output columns depend on the headers of all parts.
+    pub(crate) fn needs_all_headers(&self) -> bool {
+        matches!(self.schema, Schema::Union | Schema::Intersection)
+    }
+
+    /// Resolves the output columns from the headers of all parts.
+    pub(crate) fn resolve(&mut self, headers: &[Record]) -> Result<()> {
+        let parts: Vec<_> = headers.iter().map(describe_part).collect();
+        let headers: Vec<_> = headers
+            .iter()
+            .map(|headers| self.matcher.header_columns(headers))
+            .collect();
+        let mut columns: Vec<Column> = Vec::new();
+        match self.schema {
+            Schema::Union => {
+                for (part, found) in parts.iter().zip(headers) {
+                    let matched =
+                        self.matcher.matches(&found, &columns, part)?;
+                    columns.extend(
+                        found
+                            .into_iter()
+                            .zip(matched)
+                            .filter(|(_, j)| j.is_none())
+                            .map(|(column, _)| column),
+                    );
+                }
+            }
+            Schema::Intersection => {
+                if let Some((first, rest)) = headers.split_first() {
+                    let mut kept = vec![true; first.len()];
+                    for (part, found) in parts[1..].iter().zip(rest) {
+                        let matched =
+                            self.matcher.matches(found, first, part)?;
+                        let mut present = vec![false; first.len()];
+                        matched
+                            .into_iter()
+                            .flatten()
+                            .for_each(|j| present[j] = true);
+                        kept.iter_mut()
+                            .zip(present)
+                            .for_each(|(k, p)| *k &= p);
+                    }
+                    columns = first
+                        .iter()
+                        .zip(kept)
+                        .filter(|(_, kept)| *kept)
+                        .map(|(column, _)| column.clone())
+                        .collect();
+                }
+            }
+            Schema::First | Schema::Explicit(_) => return Ok(()),
+        }
+        self.columns = Some(columns);
+        Ok(())
+    }
+
+    /// Maps the output columns to the fields of the part with the given `headers`, and replaces
+    /// `headers` with the output header.
+    pub(crate) fn set_headers(&mut self, headers: &mut Record) -> Result<()> {
+        let found = self.matcher.header_columns(headers);
+        let columns = self.columns.get_or_insert_with(|| found.clone());
+        let part = describe_part(headers);
+        let matched = self.matcher.matches(&found, columns, &part)?;
+        self.indexes = vec![None; columns.len()];
+        for (i, j) in matched.into_iter().enumerate() {
+            if let Some(j) = j {
+                self.indexes[j] = Some(i);
+            }
+        }
+
+        let cells = headers.as_xlsx_record_mut();
+        cells.clear();
+        cells.extend(
+            columns.iter().map(|column| DataType::String(column.name.clone())),
+        );
+        Ok(())
+    }
+
+    /// Rearranges the fields of `record` in the order of the output columns.
+    pub(crate) fn align(&mut self, record: &mut Record) {
+        self.buf.clone_from(record);
+        let cells = record.as_xlsx_record_mut();
+        cells.clear();
+        for index in self.indexes.iter() {
+            let field = index.and_then(|i| self.buf.get(i));
+            cells.push(field.map_or(DataType::Empty, |f| f.to_data_type()));
+        }
+    }
+}
+
+/// Describes the part that `headers` were read from in error messages, e.g. `source 2` or
+/// `` worksheet `Data` of source 2 ``.
+fn describe_part(headers: &Record) -> String {
+    let source = match headers.source() {
+        Some(source) => format!("source {}", source + 1),
+        None => "a source".to_string(),
+    };
+    match headers.sheet() {
+        Some(sheet) => format!("worksheet `{}` of {}", sheet, source),
+        None => source,
+    }
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn matcher(normalize: Normalize, distance: usize) -> Matcher {
+        Matcher { normalize, distance, ..Matcher::default() }
+    }
+
+    fn columns(matcher: &Matcher, names: &[&str]) -> Vec<Column> {
+        matcher.columns(names.iter().map(|name| name.to_string()))
+    }
+
+    fn matches(
+        matcher: &Matcher,
+        found: &[&str],
+        columns_: &[&str],
+    ) -> Result<Vec<Option<usize>>> {
+        matcher.matches(
+            &columns(matcher, found),
+            &columns(matcher, columns_),
+            "source 2",
+        )
+    }
+
+    fn error(result: Result<Vec<Option<usize>>>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn matches_within_distance() {
+        let m = matcher(Normalize::None, 1);
+        assert_eq!(
+            matches(&m, &["Qtys", "Price"], &["Qty", "Price"]).unwrap(),
+            vec![Some(0), Some(1)]
+        );
+        let m = matcher(Normalize::None, 0);
+        assert_eq!(
+            matches(&m, &["Qtys", "Price"], &["Qty", "Price"]).unwrap(),
+            vec![None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_columns() {
+        let m = matcher(Normalize::None, 1);
+        assert_eq!(
+            error(matches(&m, &["a"], &["ab", "ac"])),
+            "the header `a` of source 2 ambiguously matches the columns `ab`, `ac`"
+        );
+    }
+
+    #[test]
+    fn rejects_rivals_for_a_column() {
+        let m = matcher(Normalize::None, 1);
+        assert_eq!(
+            error(matches(&m, &["ab", "ac"], &["a"])),
+            "the headers `ab` and `ac` of source 2 ambiguously match the column `a`"
+        );
+    }
+
+    #[test]
+    fn rejects_near_misses() {
+        let m = matcher(Normalize::None, 1);
+        let err = error(matches(&m, &["Dxte1"], &["Date"]));
+        assert!(
+            err.starts_with(
+                "the header `Dxte1` of source 2 doesn't match any column closely enough"
+            ),
+            "{}",
+            err
+        );
+        assert!(err.ends_with("Did you mean `Date`?"), "{}", err);
+
+        // a distant header is simply a different column.
+        assert_eq!(matches(&m, &["Amount"], &["Date"]).unwrap(), vec![None]);
+    }
+
+    #[test]
+    fn matches_occurrences_of_duplicate_names() {
+        let m = matcher(Normalize::None, 0);
+        assert_eq!(
+            matches(&m, &["a", "a"], &["a", "b", "a"]).unwrap(),
+            vec![Some(0), Some(2)]
+        );
+        assert_eq!(
+            matches(&m, &["a", "a"], &["a", "b"]).unwrap(),
+            vec![Some(0), None]
+        );
+    }
+
+    #[test]
+    fn normalizes_names() {
+        let name = "  Order_No.  Total ";
+        assert_eq!(Normalize::None.apply(name), name);
+        assert_eq!(Normalize::Whitespace.apply(name), "Order_No. Total");
+        assert_eq!(Normalize::Case.apply(name), "order_no. total");
+        assert_eq!(Normalize::Alphanumeric.apply(name), "ordernototal");
+
+        let found = ["order no", "Order_No", " Order No "];
+        let expected = [
+            (Normalize::None, vec![None, None, None]),
+            (Normalize::Whitespace, vec![None, None, Some(0)]),
+            (Normalize::Case, vec![Some(0), None, Some(0)]),
+            (Normalize::Alphanumeric, vec![Some(0), Some(0), Some(0)]),
+        ];
+        for (normalize, expected) in expected {
+            let m = matcher(normalize, 0);
+            for (name, expected) in found.iter().zip(expected) {
+                assert_eq!(
+                    matches(&m, &[name], &["Order No"]).unwrap(),
+                    vec![expected],
+                    "{:?} {:?}",
+                    normalize,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_aliases() {
+        let mut m = matcher(Normalize::Case, 0);
+        m.alias("Quantity", "qty");
+        let m = m.build();
+        assert_eq!(
+            matches(&m, &["QUANTITY"], &["Qty"]).unwrap(),
+            vec![Some(0)]
+        );
+    }
+
+    #[test]
+    fn describes_parts() {
+        let mut headers: Record = ["a"].into_iter().collect();
+        assert_eq!(describe_part(&headers), "a source");
+        headers.set_source(Some(1));
+        assert_eq!(describe_part(&headers), "source 2");
+        headers.set_sheet(Some(Arc::from("Data")));
+        assert_eq!(describe_part(&headers), "worksheet `Data` of source 2");
+    }
+}
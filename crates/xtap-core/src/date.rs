@@ -0,0 +1,185 @@
+use std::fmt::Write as _;
+use std::io;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+/// The number of milliseconds in a day.
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// The date system that Excel serial date-times count days in.
+///
+/// Spreadsheets are always read in the 1900 date system, i.e. the serials of xlsx workbooks using
+/// the 1904 date system are converted when reading them, except for time-only values. `V1904` is
+/// therefore only needed for serials from elsewhere.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DateSystem {
+    /// Counts days from `1900-01-01`, which is serial `1`.
+    ///
+    /// This system treats 1900 as a leap year for compatibility with Lotus 1-2-3, hence serial
+    /// `60` is the nonexistent `1900-02-29`, and the serials before it are off by one day.
+    #[default]
+    V1900,
+    /// Counts days from `1904-01-01`, which is serial `0`, as used by early versions of Excel for
+    /// Mac.
+    V1904,
+}
+
+impl DateSystem {
+    /// The difference between the serials of the same date-time in the two date systems.
+    pub(crate) const OFFSET: f64 = 1462.0;
+
+    /// Converts the serial `serial` in this date system into a date-time, rounded to
+    /// milliseconds.
+    ///
+    /// A serial less than `1` in the 1900 date system is a time without a date, which is
+    /// returned on the day `1899-12-31` that Excel displays as `1900-01-00`.
+    ///
+    /// Returns `None` if `serial` is negative, is the nonexistent `1900-02-29`, or is out of the
+    /// range of `NaiveDateTime`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use xtap_core::DateSystem;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+    /// let noon = date.and_hms_opt(12, 0, 0).unwrap();
+    /// assert_eq!(DateSystem::V1900.to_datetime(44197.5), Some(noon));
+    /// assert_eq!(DateSystem::V1904.to_datetime(42735.5), Some(noon));
+    /// assert_eq!(DateSystem::V1900.to_datetime(60.0), None);
+    /// ```
+    pub fn to_datetime(self, serial: f64) -> Option<NaiveDateTime> {
+        if !serial.is_finite() || serial < 0.0 {
+            return None;
+        }
+        let ms = (serial * MS_PER_DAY as f64).round() as i64;
+        let (days, ms) = (ms / MS_PER_DAY, ms % MS_PER_DAY);
+        let epoch = match self {
+            DateSystem::V1900 if days < 60 => ymd(1899, 12, 31),
+            DateSystem::V1900 if days == 60 => return None,
+            DateSystem::V1900 => ymd(1899, 12, 30),
+            DateSystem::V1904 => ymd(1904, 1, 1),
+        };
+        epoch
+            .checked_add_signed(Duration::days(days))?
+            .checked_add_signed(Duration::milliseconds(ms))
+    }
+
+    /// Converts `datetime` into a serial in this date system.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use xtap_core::DateSystem;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap();
+    /// let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    /// assert_eq!(DateSystem::V1900.to_serial(midnight), 1.0);
+    /// ```
+    pub fn to_serial(self, datetime: NaiveDateTime) -> f64 {
+        let epoch = match self {
+            DateSystem::V1900 if datetime < ymd(1900, 3, 1) => {
+                ymd(1899, 12, 31)
+            }
+            DateSystem::V1900 => ymd(1899, 12, 30),
+            DateSystem::V1904 => ymd(1904, 1, 1),
+        };
+        let ms = (datetime - epoch).num_milliseconds();
+        ms as f64 / MS_PER_DAY as f64
+    }
+}
+
+/// Returns the midnight of the given date, which must be valid.
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap()
+}
+
+/// The format of date-time cells in csv output.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DateFormat {
+    /// Writes the serial number as is, e.g. `44197.5`.
+    #[default]
+    Serial,
+    /// Writes an ISO 8601 date if there's no time part, e.g. `2021-01-01`, a time if there's no
+    /// date part, e.g. `12:00:00`, and a date and time otherwise, e.g. `2021-01-01T12:00:00`.
+    Iso8601,
+    /// Writes with the given `strftime`-style format of `chrono`, e.g. `%Y/%m/%d %H:%M`.
+    Custom(String),
+}
+
+impl DateFormat {
+    /// Formats the serial `serial` in the 1900 date system, or returns `None` if it should be
+    /// written as is.
+    pub(crate) fn format(&self, serial: f64) -> Option<String> {
+        if *self == DateFormat::Serial {
+            return None;
+        }
+        let datetime = DateSystem::V1900.to_datetime(serial)?;
+        let midnight = datetime.date().and_hms_opt(0, 0, 0) == Some(datetime);
+        let format = match self {
+            DateFormat::Iso8601 if serial < 1.0 => "%H:%M:%S%.f",
+            DateFormat::Iso8601 if midnight => "%Y-%m-%d",
+            DateFormat::Iso8601 => "%Y-%m-%dT%H:%M:%S%.f",
+            DateFormat::Custom(format) => format,
+            DateFormat::Serial => unreachable!(),
+        };
+
+        // an invalid custom format fails to be written rather than to be parsed.
+        let mut buf = String::new();
+        write!(buf, "{}", datetime.format(format)).ok()?;
+        Some(buf)
+    }
+
+    /// Returns an error if this is a custom format that can't be written, e.g. because of an
+    /// unknown specifier like `%Q` or a time zone specifier like `%z`.
+    pub(crate) fn validate(&self) -> io::Result<()> {
+        if let DateFormat::Custom(format) = self {
+            let datetime = DateSystem::V1900.to_datetime(44197.5).unwrap();
+            let mut buf = String::new();
+            if write!(buf, "{}", datetime.format(format)).is_err() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid date format `{}`", format),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_serials_around_the_1900_leap_day() {
+        for serial in [0.5, 1.0, 59.0, 59.75, 61.0, 61.25, 44197.5] {
+            let datetime = DateSystem::V1900.to_datetime(serial).unwrap();
+            assert_eq!(DateSystem::V1900.to_serial(datetime), serial);
+        }
+        assert_eq!(DateSystem::V1900.to_datetime(60.0), None);
+        assert_eq!(DateSystem::V1900.to_datetime(60.5), None);
+        assert_eq!(DateSystem::V1900.to_datetime(-1.0), None);
+    }
+
+    #[test]
+    fn round_trips_1904_serials() {
+        for serial in [0.0, 0.5, 59.0, 60.0, 42735.5] {
+            let datetime = DateSystem::V1904.to_datetime(serial).unwrap();
+            assert_eq!(DateSystem::V1904.to_serial(datetime), serial);
+            assert_eq!(
+                DateSystem::V1900.to_serial(datetime),
+                serial + DateSystem::OFFSET
+            );
+        }
+        assert_eq!(
+            DateSystem::V1904.to_datetime(59.0),
+            Some(ymd(1904, 2, 29))
+        );
+    }
+}
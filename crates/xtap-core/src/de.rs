@@ -0,0 +1,770 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::iter::Peekable;
+
+use chrono::NaiveDateTime;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer,
+    MapAccess, SeqAccess, Visitor,
+};
+
+use crate::{Field, Record, RecordIter};
+
+/// The format date-time cells are deserialized as, which `NaiveDateTime` parses.
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// An error that occurred while deserializing a record.
+///
+/// It is wrapped in an `io::Error` of kind `InvalidData` when returned from
+/// [`Record::deserialize`](crate::Record::deserialize), and can be retrieved with
+/// `io::Error::get_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeError {
+    row: Option<u64>,
+    field: Option<u64>,
+    msg: String,
+}
+
+impl DeserializeError {
+    /// Returns the 1-based line or row number of the record, if known.
+    pub fn row(&self) -> Option<u64> {
+        self.row
+    }
+
+    /// Returns the 1-based index of the field that failed to deserialize, if any.
+    pub fn field(&self) -> Option<u64> {
+        self.field
+    }
+
+    /// Returns the message of this error.
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.row, self.field) {
+            (Some(row), Some(field)) => {
+                write!(f, "row {}, field {}: ", row, field)?
+            }
+            (Some(row), None) => write!(f, "row {}: ", row)?,
+            (None, Some(field)) => write!(f, "field {}: ", field)?,
+            (None, None) => {}
+        }
+        f.write_str(&self.msg)
+    }
+}
+
+impl StdError for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> DeserializeError {
+        DeserializeError { row: None, field: None, msg: msg.to_string() }
+    }
+}
+
+/// Deserializes `record` into `D`, using the field names in `headers` if given.
+pub(crate) fn deserialize_record<'de, D: Deserialize<'de>>(
+    record: &'de Record,
+    headers: Option<&'de Record>,
+) -> io::Result<D> {
+    let mut de = DeRecord {
+        fields: record.iter().peekable(),
+        headers: headers.map(Record::iter),
+        field: 0,
+    };
+    D::deserialize(&mut de).map_err(|mut err| {
+        err.row = record.row();
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    })
+}
+
+/// A deserializer over the fields of a record.
+struct DeRecord<'r> {
+    fields: Peekable<RecordIter<'r>>,
+    headers: Option<RecordIter<'r>>,
+    /// The number of fields consumed so far.
+    field: u64,
+}
+
+impl<'r> DeRecord<'r> {
+    fn next_field(&mut self) -> Result<DeField<'r>, DeserializeError> {
+        match self.fields.next() {
+            Some(field) => {
+                self.field += 1;
+                Ok(DeField(field))
+            }
+            None => Err(de::Error::custom(
+                "expected a field, but found the end of the record",
+            )),
+        }
+    }
+
+    /// Attaches the index of the last consumed field to `err`.
+    fn at(&self, mut err: DeserializeError) -> DeserializeError {
+        err.field.get_or_insert(self.field);
+        err
+    }
+}
+
+/// Deserializes the next field of the record with the given method.
+macro_rules! deserialize_field {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'r>>(
+                self,
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                let field = self.next_field()?;
+                field.$method(visitor).map_err(|err| self.at(err))
+            }
+        )*
+    };
+}
+
+impl<'r> Deserializer<'r> for &mut DeRecord<'r> {
+    type Error = DeserializeError;
+
+    deserialize_field!(
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_identifier
+    );
+
+    fn deserialize_any<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.headers.is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.fields.peek() {
+            None => visitor.visit_none(),
+            Some(field) if field.is_empty() => {
+                self.next_field()?;
+                visitor.visit_none()
+            }
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'r>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_map<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.headers.is_none() {
+            return Err(de::Error::custom(
+                "deserializing a record into a map requires headers",
+            ));
+        }
+        visitor.visit_map(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.headers.is_some() {
+            visitor.visit_map(self)
+        } else {
+            visitor.visit_seq(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'r>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let field = self.next_field()?;
+        field
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|err| self.at(err))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.next_field()?;
+        visitor.visit_unit()
+    }
+}
+
+impl<'r> SeqAccess<'r> for &mut DeRecord<'r> {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'r>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.fields.peek().is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(&mut **self).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+impl<'r> MapAccess<'r> for &mut DeRecord<'r> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'r>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.headers.as_mut().and_then(Iterator::next) {
+            Some(header) => seed.deserialize(DeField(header)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'r>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(&mut **self)
+    }
+}
+
+/// A deserializer over a single field.
+struct DeField<'r>(Field<'r>);
+
+impl<'r> DeField<'r> {
+    /// Returns the text of this field if it is bytes or a string.
+    fn text(&self) -> Result<Option<&'r str>, DeserializeError> {
+        match self.0 {
+            Field::Bytes(bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => Ok(Some(s)),
+                Err(err) => Err(de::Error::custom(err)),
+            },
+            Field::Str(s) => Ok(Some(s)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns this field as it is written into csv output, except that date-times are
+    /// formatted as [`DATETIME_FORMAT`].
+    fn formatted(&self) -> Result<String, DeserializeError> {
+        match self.0 {
+            Field::DateTime(serial) => match self.0.as_datetime() {
+                Some(datetime) => {
+                    Ok(datetime.format(DATETIME_FORMAT).to_string())
+                }
+                None => Err(de::Error::custom(format!(
+                    "invalid date-time `{}`",
+                    serial
+                ))),
+            },
+            Field::Error(err) => Err(cell_error(err)),
+            ref field => Ok(field.to_display_string()),
+        }
+    }
+}
+
+/// Parses the text of a date-time field, which is either `format` or [`DATETIME_FORMAT`], and
+/// returns the part of it `part` extracts.
+fn parse_datetime_part<T, E: de::Error>(
+    s: &str,
+    format: &str,
+    part: fn(NaiveDateTime) -> T,
+    parse: fn(&str, &str) -> chrono::ParseResult<T>,
+) -> Result<T, E> {
+    let s = s.trim();
+    parse(s, format)
+        .or_else(|err| {
+            NaiveDateTime::parse_from_str(s, DATETIME_FORMAT)
+                .map(part)
+                .map_err(|_| err)
+        })
+        .map_err(|err| {
+            E::custom(format!("invalid date-time `{}`: {}", s, err))
+        })
+}
+
+/// (De)serializes a `chrono::NaiveDate` as the date of a field, for use with
+/// `#[serde(with = "xtap_core::naive_date")]`.
+///
+/// Date-time cells are deserialized as date-times, which `NaiveDate` itself doesn't parse. With
+/// this, the date part of a date-time cell is taken instead, as [`Field::as_date`] does, and text
+/// such as `2021-01-31` is parsed as usual.
+pub mod naive_date {
+    use chrono::{NaiveDate, NaiveDateTime};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes `date` as `%Y-%m-%d`.
+    pub fn serialize<S: Serializer>(
+        date: &NaiveDate,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format("%Y-%m-%d"))
+    }
+
+    /// Deserializes a date from a date-time cell or from text.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NaiveDate, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_datetime_part(
+            &s,
+            "%Y-%m-%d",
+            |datetime: NaiveDateTime| datetime.date(),
+            NaiveDate::parse_from_str,
+        )
+    }
+}
+
+/// (De)serializes a `chrono::NaiveTime` as the time of a field, for use with
+/// `#[serde(with = "xtap_core::naive_time")]`.
+///
+/// Date-time cells are deserialized as date-times, which `NaiveTime` itself doesn't parse. With
+/// this, the time part of a date-time cell is taken instead, as [`Field::as_time`] does, and text
+/// such as `12:30:00` is parsed as usual.
+pub mod naive_time {
+    use chrono::{NaiveDateTime, NaiveTime};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes `time` as `%H:%M:%S%.f`.
+    pub fn serialize<S: Serializer>(
+        time: &NaiveTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&time.format("%H:%M:%S%.f"))
+    }
+
+    /// Deserializes a time from a date-time cell or from text.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NaiveTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_datetime_part(
+            &s,
+            "%H:%M:%S%.f",
+            |datetime: NaiveDateTime| datetime.time(),
+            NaiveTime::parse_from_str,
+        )
+    }
+}
+
+fn cell_error(err: &calamine::CellErrorType) -> DeserializeError {
+    de::Error::custom(format!("the cell contains the error `{}`", err))
+}
+
+/// Deserializes an integer from an integer cell, a float cell without a fractional part that
+/// fits in the target type, or a string.
+macro_rules! deserialize_int {
+    ($($method:ident, $visit:ident, $ty:ty);*) => {
+        $(
+            fn $method<V: Visitor<'r>>(
+                self,
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                match self.0 {
+                    Field::Int(int) => visitor.visit_i64(int),
+                    Field::Float(float) if float.fract() == 0.0 => {
+                        // `MAX as f64` may round up to the next power of two, which is out of
+                        // range, as is adding one to it.
+                        let min = <$ty>::MIN as f64;
+                        let end = <$ty>::MAX as f64 + 1.0;
+                        if float >= min && float < end {
+                            visitor.$visit(float as $ty)
+                        } else {
+                            Err(de::Error::custom(format!(
+                                "integer `{}` is out of range for `{}`",
+                                float,
+                                stringify!($ty)
+                            )))
+                        }
+                    }
+                    _ => match self.text()? {
+                        Some(s) => match s.parse::<$ty>() {
+                            Ok(int) => visitor.$visit(int),
+                            Err(err) => Err(de::Error::custom(format!(
+                                "invalid integer `{}`: {}",
+                                s, err
+                            ))),
+                        },
+                        None => self.deserialize_any(visitor),
+                    },
+                }
+            }
+        )*
+    };
+}
+
+/// Deserializes a float from a numeric cell or a string.
+macro_rules! deserialize_float {
+    ($($method:ident, $visit:ident, $ty:ty);*) => {
+        $(
+            fn $method<V: Visitor<'r>>(
+                self,
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                match self.0 {
+                    Field::Int(int) => visitor.visit_f64(int as f64),
+                    Field::Float(float) | Field::DateTime(float) => {
+                        visitor.visit_f64(float)
+                    }
+                    _ => match self.text()? {
+                        Some(s) => match s.parse::<$ty>() {
+                            Ok(float) => visitor.$visit(float),
+                            Err(err) => Err(de::Error::custom(format!(
+                                "invalid float `{}`: {}",
+                                s, err
+                            ))),
+                        },
+                        None => self.deserialize_any(visitor),
+                    },
+                }
+            }
+        )*
+    };
+}
+
+impl<'r> Deserializer<'r> for DeField<'r> {
+    type Error = DeserializeError;
+
+    deserialize_int!(
+        deserialize_i8, visit_i8, i8;
+        deserialize_i16, visit_i16, i16;
+        deserialize_i32, visit_i32, i32;
+        deserialize_i64, visit_i64, i64;
+        deserialize_i128, visit_i128, i128;
+        deserialize_u8, visit_u8, u8;
+        deserialize_u16, visit_u16, u16;
+        deserialize_u32, visit_u32, u32;
+        deserialize_u64, visit_u64, u64;
+        deserialize_u128, visit_u128, u128
+    );
+
+    deserialize_float!(
+        deserialize_f32, visit_f32, f32;
+        deserialize_f64, visit_f64, f64
+    );
+
+    fn deserialize_any<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Field::Bytes(bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(bytes),
+            },
+            Field::Str(s) => visitor.visit_borrowed_str(s),
+            Field::Int(int) => visitor.visit_i64(int),
+            Field::Float(float) => visitor.visit_f64(float),
+            Field::Bool(boolean) => visitor.visit_bool(boolean),
+            Field::DateTime(_) => visitor.visit_string(self.formatted()?),
+            Field::Error(err) => Err(cell_error(err)),
+            Field::Empty => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Field::Bool(boolean) => visitor.visit_bool(boolean),
+            _ => match self.text()? {
+                Some(s) => match s.parse() {
+                    Ok(boolean) => visitor.visit_bool(boolean),
+                    Err(_) => Err(de::Error::custom(format!(
+                        "invalid bool `{}`, expected `true` or `false`",
+                        s
+                    ))),
+                },
+                None => self.deserialize_any(visitor),
+            },
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let s = self.formatted()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(de::Error::custom(format!(
+                "expected a single character but found `{}`",
+                s
+            ))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.text()? {
+            Some(s) => visitor.visit_borrowed_str(s),
+            None => visitor.visit_string(self.formatted()?),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Field::Bytes(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Field::Str(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+            _ => visitor.visit_byte_buf(self.formatted()?.into_bytes()),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::custom("expected an empty field"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'r>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant: String = self.formatted()?;
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    fn deserialize_identifier<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'r>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        <W: Visitor<'r>>
+        seq tuple tuple_struct map struct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::DataType;
+
+    use super::*;
+
+    /// Deserializes a record with the given cells into `D`.
+    fn deserialize<D: for<'de> Deserialize<'de>>(
+        cells: Vec<DataType>,
+    ) -> io::Result<D> {
+        Record::from(cells).deserialize(None)
+    }
+
+    #[test]
+    fn deserializes_integers_from_whole_floats() {
+        let cells = vec![DataType::Float(3.0), DataType::Float(-2.0)];
+        assert_eq!(deserialize::<(u8, i64)>(cells).unwrap(), (3, -2));
+
+        let err = deserialize::<(u8,)>(vec![DataType::Float(300.0)]);
+        assert!(err.is_err());
+        let err = deserialize::<(i64,)>(vec![DataType::Float(3.5)]);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn rejects_floats_out_of_the_integer_range() {
+        for float in [1e19, -1e19, f64::INFINITY, f64::NAN] {
+            let err = deserialize::<(i64,)>(vec![DataType::Float(float)])
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let err = deserialize::<(u8,)>(vec![DataType::Float(256.0)]);
+        assert!(err.is_err());
+        let err = deserialize::<(u64,)>(vec![DataType::Float(-1.0)]);
+        assert!(err.is_err());
+        let err = deserialize::<(u64,)>(vec![DataType::Float(2f64.powi(64))]);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn deserializes_whole_floats_into_each_integer_type() {
+        let ok = deserialize::<(u64,)>(vec![DataType::Float(1e19)]);
+        assert_eq!(ok.unwrap(), (10_000_000_000_000_000_000,));
+        let ok = deserialize::<(u8, i8)>(vec![
+            DataType::Float(255.0),
+            DataType::Float(-128.0),
+        ]);
+        assert_eq!(ok.unwrap(), (255, -128));
+        let ok = deserialize::<(i128,)>(vec![DataType::Float(-1e30)]);
+        assert_eq!(ok.unwrap(), (-1_000_000_000_000_000_019_884_624_838_656,));
+    }
+
+    #[test]
+    fn deserializes_date_times_in_one_format() {
+        let cells = vec![
+            DataType::DateTime(44227.0),
+            DataType::DateTime(44227.5),
+            DataType::DateTime(0.5),
+        ];
+        let (date, datetime, time): (String, String, String) =
+            deserialize(cells.clone()).unwrap();
+        assert_eq!(date, "2021-01-31T00:00:00");
+        assert_eq!(datetime, "2021-01-31T12:00:00");
+        assert_eq!(time, "1899-12-31T12:00:00");
+
+        let datetimes: (NaiveDateTime, NaiveDateTime, NaiveDateTime) =
+            deserialize(cells).unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2021, 1, 31).unwrap();
+        assert_eq!(datetimes.0, date.and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(datetimes.1, date.and_hms_opt(12, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn deserializes_dates_and_times_with_helpers() {
+        let date = chrono::NaiveDate::from_ymd_opt(2021, 1, 31).unwrap();
+        let noon = chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        for field in [
+            Field::DateTime(44227.0),
+            Field::DateTime(44227.5),
+            Field::Str("2021-01-31"),
+            Field::Bytes(b"2021-01-31T12:00:00"),
+        ] {
+            assert_eq!(naive_date::deserialize(DeField(field)), Ok(date));
+        }
+        for field in [
+            Field::DateTime(0.5),
+            Field::DateTime(44227.5),
+            Field::Str("12:00:00"),
+        ] {
+            assert_eq!(naive_time::deserialize(DeField(field)), Ok(noon));
+        }
+
+        assert!(naive_date::deserialize(DeField(Field::Str("31/01/2021")))
+            .is_err());
+        assert!(naive_time::deserialize(DeField(Field::Int(3))).is_err());
+    }
+}
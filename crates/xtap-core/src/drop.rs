@@ -1,22 +1,20 @@
 /// The columns and rows drop hehaviour.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Drop {
-    /// Drops a number of rows from the head of each source. The second field of this tuple
-    /// indicates whether the head of the first source should be preserved or not.
-    head: usize,
-    /// Drops a number of rows from the tail of each source. The second field of this tuple
-    /// indicates whether the tail of the last source should be preserved or not.
-    tail: usize,
+    /// Drops a number of rows from the head of each source.
+    pub(crate) head: usize,
+    /// Drops a number of rows from the tail of each source.
+    pub(crate) tail: usize,
     /// Drops any row if its length is not the longest.
-    non_max_length: bool,
+    pub(crate) non_max_length: bool,
     /// Drops any row if its length is shorter than the given one.
-    length_less_than: Option<usize>,
+    pub(crate) length_less_than: Option<usize>,
     /// Drops any row if its `nth` (zero-based) field is empty.
     ///
-    /// Both `Field::Binary([])` and `Field::Empty` are considered empty.
-    fields_empty: Option<Vec<usize>>,
-    /// Drops the given columns.
-    columns: Option<Vec<usize>>,
+    /// Both `Field::Bytes([])` and `Field::Empty` are considered empty.
+    pub(crate) fields_empty: Option<Vec<usize>>,
+    /// Drops the given (zero-based) columns.
+    pub(crate) columns: Option<Vec<usize>>,
 }
 
 impl Drop {
@@ -25,14 +23,12 @@ impl Drop {
         Default::default()
     }
 
-    /// Drops a given number of rows from the head of each file. If `trailing_only` was given
-    /// `true`, then the head of the the first file is preserved.
+    /// Drops a given number of rows from the head of each file.
     pub fn drop_head(self, count: usize) -> Self {
         Drop { head: count, ..self }
     }
 
-    /// Drops a given number of rows from the tail of each file. If `leading_only` was given
-    /// `true`, then the tail of the last file is preserved.
+    /// Drops a given number of rows from the tail of each file.
     pub fn drop_tail(self, count: usize) -> Self {
         Drop { tail: count, ..self }
     }
@@ -54,7 +50,7 @@ impl Drop {
         Drop { fields_empty: Some(indexes), ..self }
     }
 
-    /// Drops the given columns.
+    /// Drops the given (zero-based) columns from every row, including the header.
     pub fn drop_columns(self, indexes: Vec<usize>) -> Self {
         Drop { columns: Some(indexes), ..self }
     }
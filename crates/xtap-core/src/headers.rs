@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use xtap_util::closest_msg;
+
+use crate::Record;
+
+/// The column names of a header record, which maps each name to the index of its column.
+///
+/// Names are looked up exactly first, then case-insensitively. A name that appears multiple times
+/// in the header is renamed with a numeric suffix from its second occurrence on, e.g. the columns
+/// `name`, `name` and `name` are named `name`, `name_2` and `name_3`.
+///
+/// # Examples
+///
+/// ```
+/// use xtap_core::{Field, Headers, Record};
+///
+/// let headers = Headers::new(&Record::from_iter(vec!["id", "Name", "name"]));
+/// let record = Record::from_iter(vec!["1", "apple", "pear"]);
+/// assert_eq!(record.get_by_name(&headers, "id")?, Some(Field::Bytes(b"1")));
+/// assert_eq!(record.get_by_name(&headers, "ID")?, Some(Field::Bytes(b"1")));
+/// assert_eq!(record.get_by_name(&headers, "name")?, Some(Field::Bytes(b"pear")));
+///
+/// let err = record.get_by_name(&headers, "ids").unwrap_err();
+/// assert_eq!(err.to_string(), "header `ids` not found\n\n\tDid you mean `id`?");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Headers {
+    /// The unique names of the columns.
+    names: Vec<String>,
+    /// The index of the column of each name.
+    index: HashMap<String, usize>,
+    /// The index of the column of each lowercase name, or `None` if multiple names are the same
+    /// ignoring case.
+    folded: HashMap<String, Option<usize>>,
+}
+
+impl Headers {
+    /// Creates the column names of the header record `headers`.
+    ///
+    /// Fields are named by their text, see [`Field::as_str_lossy`].
+    ///
+    /// [`Field::as_str_lossy`]: crate::Field::as_str_lossy
+    pub fn new(headers: &Record) -> Headers {
+        let originals: Vec<String> = headers
+            .iter()
+            .map(|field| field.as_str_lossy().into_owned())
+            .collect();
+        let reserved: HashSet<&str> =
+            originals.iter().map(String::as_str).collect();
+
+        let mut names = Vec::with_capacity(originals.len());
+        let mut index = HashMap::with_capacity(originals.len());
+        for original in originals.iter() {
+            let mut name = original.clone();
+            let mut n = 1;
+            // a suffixed name never shadows a name that appears in the header itself.
+            while index.contains_key(&name)
+                || (n > 1 && reserved.contains(name.as_str()))
+            {
+                n += 1;
+                name = format!("{}_{}", original, n);
+            }
+            index.insert(name.clone(), names.len());
+            names.push(name);
+        }
+
+        let mut folded = HashMap::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            folded
+                .entry(name.to_lowercase())
+                .and_modify(|found| *found = None)
+                .or_insert(Some(i));
+        }
+        Headers { names, index, folded }
+    }
+
+    /// Returns the number of columns.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if there's no column.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the unique names of the columns in order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the unique name of the column at index `i`, if any.
+    pub fn name(&self, i: usize) -> Option<&str> {
+        self.names.get(i).map(String::as_str)
+    }
+
+    /// Returns the index of the column named `name`.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `NotFound` is returned if there's no such column, which suggests the
+    /// closest name if any. If `name` only matches multiple names ignoring case, an error of kind
+    /// `InvalidInput` is returned.
+    pub fn index(&self, name: &str) -> io::Result<usize> {
+        if let Some(&i) = self.index.get(name) {
+            return Ok(i);
+        }
+        match self.folded.get(&name.to_lowercase()) {
+            Some(&Some(i)) => Ok(i),
+            Some(None) => {
+                let folded = name.to_lowercase();
+                let names: Vec<_> = self
+                    .names
+                    .iter()
+                    .filter(|n| n.to_lowercase() == folded)
+                    .map(|n| format!("`{}`", n))
+                    .collect();
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "header `{}` ambiguously matches the headers {}",
+                        name,
+                        names.join(", ")
+                    ),
+                ))
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "header `{}` not found{}",
+                    name,
+                    closest_msg(name, self.names.iter(), |n| n.as_str())
+                ),
+            )),
+        }
+    }
+}
+
+impl From<&Record> for Headers {
+    fn from(headers: &Record) -> Headers {
+        Headers::new(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Headers {
+        Headers::new(&Record::from_iter(names.iter().copied()))
+    }
+
+    #[test]
+    fn suffixes_duplicate_names() {
+        assert_eq!(headers(&["a", "a", "a"]).names(), ["a", "a_2", "a_3"]);
+        assert_eq!(headers(&["a", "a", "a_2"]).names(), ["a", "a_3", "a_2"]);
+        assert_eq!(headers(&["a_2", "a", "a"]).names(), ["a_2", "a", "a_3"]);
+        assert_eq!(
+            headers(&["a", "a_2", "a_2", "a"]).names(),
+            ["a", "a_2", "a_2_2", "a_3"]
+        );
+
+        let headers = headers(&["a", "a", "a_2"]);
+        assert_eq!(headers.index("a_2").unwrap(), 2);
+        assert_eq!(headers.index("a_3").unwrap(), 1);
+        assert_eq!(headers.name(1), Some("a_3"));
+    }
+
+    #[test]
+    fn looks_up_names_ignoring_case() {
+        let headers = headers(&["id", "Name", "NAME"]);
+        assert_eq!(headers.index("ID").unwrap(), 0);
+        assert_eq!(headers.index("Name").unwrap(), 1);
+        assert_eq!(headers.index("NAME").unwrap(), 2);
+
+        let err = headers.index("name").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "header `name` ambiguously matches the headers `Name`, `NAME`"
+        );
+
+        let err = headers.index("title").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "header `title` not found");
+    }
+
+    #[test]
+    fn looks_up_suffixed_names_ignoring_case() {
+        let distinct = headers(&["Name", "name"]);
+        assert_eq!(distinct.names(), ["Name", "name"]);
+        assert!(distinct.index("NAME").is_err());
+
+        let suffixed = headers(&["Name", "Name"]);
+        assert_eq!(suffixed.index("NAME_2").unwrap(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+use std::mem;
+
+use calamine::DataType;
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{DateSystem, Record};
+
+/// The rules of inferring the types of csv fields, so that csv records have typed fields like
+/// spreadsheet records do.
+///
+/// A field is inferred as the first type its text is a valid value of, in the order of empty,
+/// boolean, integer, float and date-time. Any other field is kept as a string.
+///
+/// # Examples
+///
+/// ```
+/// use xtap_core::{Field, Infer, ReaderBuilder, Record};
+///
+/// let mut rdr = ReaderBuilder::new()
+///     .infer(Infer::new().date_formats(&["%Y/%m/%d"]))
+///     .from_reader("007,42,2.5,TRUE,,2021/01/01\n".as_bytes())?;
+/// let mut record = Record::default();
+/// rdr.read_record(&mut record)?;
+/// assert_eq!(record.get(0), Some(Field::Str("007")));
+/// assert_eq!(record.get(1), Some(Field::Int(42)));
+/// assert_eq!(record.get(2), Some(Field::Float(2.5)));
+/// assert_eq!(record.get(3), Some(Field::Bool(true)));
+/// assert_eq!(record.get(4), Some(Field::Empty));
+/// assert_eq!(record.get(5), Some(Field::DateTime(44197.0)));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Infer {
+    /// Infers empty fields as `Field::Empty`.
+    pub(crate) empty: bool,
+    /// Infers `true` and `false` in any case as `Field::Bool`.
+    pub(crate) bools: bool,
+    /// Infers integers as `Field::Int`.
+    pub(crate) ints: bool,
+    /// Infers decimal numbers as `Field::Float`.
+    pub(crate) floats: bool,
+    /// Keeps numbers with leading zeros such as codes as strings.
+    pub(crate) keep_leading_zeros: bool,
+    /// The `chrono` formats of date-times inferred as `Field::DateTime`.
+    pub(crate) date_formats: Vec<String>,
+}
+
+impl Default for Infer {
+    fn default() -> Infer {
+        Infer {
+            empty: true,
+            bools: true,
+            ints: true,
+            floats: true,
+            keep_leading_zeros: true,
+            date_formats: vec![
+                "%Y-%m-%d".to_string(),
+                "%Y-%m-%d %H:%M:%S".to_string(),
+                "%Y-%m-%dT%H:%M:%S".to_string(),
+            ],
+        }
+    }
+}
+
+impl Infer {
+    /// Creates a new instance of [`Infer`] that infers every type, keeps numbers with leading
+    /// zeros as strings, and recognizes ISO 8601 dates and date-times like `2021-01-31` and
+    /// `2021-01-31 08:30:00`.
+    pub fn new() -> Infer {
+        Default::default()
+    }
+
+    /// Whether empty fields are inferred as `Field::Empty`.
+    pub fn empty(self, yes: bool) -> Self {
+        Infer { empty: yes, ..self }
+    }
+
+    /// Whether `true` and `false`, in any case, are inferred as `Field::Bool`.
+    pub fn bools(self, yes: bool) -> Self {
+        Infer { bools: yes, ..self }
+    }
+
+    /// Whether integers are inferred as `Field::Int`.
+    pub fn ints(self, yes: bool) -> Self {
+        Infer { ints: yes, ..self }
+    }
+
+    /// Whether decimal numbers such as `2.5` and `1e3` are inferred as `Field::Float`.
+    ///
+    /// Integers too large for `Field::Int` are inferred as floats as well.
+    pub fn floats(self, yes: bool) -> Self {
+        Infer { floats: yes, ..self }
+    }
+
+    /// Whether numbers with leading zeros such as `007` are kept as strings, since they are
+    /// usually codes rather than quantities.
+    pub fn keep_leading_zeros(self, yes: bool) -> Self {
+        Infer { keep_leading_zeros: yes, ..self }
+    }
+
+    /// Sets the `chrono` formats of the date-times inferred as `Field::DateTime`, which are tried
+    /// in the given order. A format may contain only a date.
+    ///
+    /// Passing an empty slice disables the inference of date-times.
+    pub fn date_formats(self, formats: &[&str]) -> Self {
+        let date_formats = formats.iter().map(|f| f.to_string()).collect();
+        Infer { date_formats, ..self }
+    }
+
+    /// Returns the rules that infer only the date-times of these rules, keeping any other field
+    /// as a string, or `None` if no date-time is inferred.
+    pub(crate) fn dates_only(self) -> Option<Infer> {
+        if self.date_formats.is_empty() {
+            return None;
+        }
+        Some(Infer {
+            empty: false,
+            bools: false,
+            ints: false,
+            floats: false,
+            ..self
+        })
+    }
+
+    /// Infers the types of the fields of `record` if it is a csv record, which is then turned
+    /// into a xlsx record.
+    ///
+    /// Invalid UTF-8 sequences in fields kept as strings are replaced with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    pub(crate) fn apply(&self, record: &mut Record) {
+        let fields = match record.as_byte_record() {
+            Some(_) => mem::take(record.as_byte_record_mut()),
+            None => return,
+        };
+        let cells = record.as_xlsx_record_mut();
+        cells.clear();
+        cells.extend(fields.iter().map(|field| self.infer(field)));
+    }
+
+    /// Returns the cell inferred from `field`.
+    pub(crate) fn infer(&self, field: &[u8]) -> DataType {
+        let s = match std::str::from_utf8(field) {
+            Ok(s) => s,
+            Err(_) => {
+                return DataType::String(
+                    String::from_utf8_lossy(field).into_owned(),
+                )
+            }
+        };
+
+        if s.is_empty() {
+            return if self.empty {
+                DataType::Empty
+            } else {
+                DataType::String(String::new())
+            };
+        }
+        if self.bools {
+            if s.eq_ignore_ascii_case("true") {
+                return DataType::Bool(true);
+            } else if s.eq_ignore_ascii_case("false") {
+                return DataType::Bool(false);
+            }
+        }
+        if is_number(s) && !(self.keep_leading_zeros && has_leading_zero(s)) {
+            if let Some(int) = s.parse().ok().filter(|_| self.ints) {
+                return DataType::Int(int);
+            }
+            if let Some(float) = s.parse().ok().filter(|_| self.floats) {
+                return DataType::Float(float);
+            }
+        }
+        if let Some(serial) = self.parse_date_time(s) {
+            return DataType::DateTime(serial);
+        }
+        DataType::String(s.to_string())
+    }
+
+    /// Returns the Excel serial of `s` if it matches any of the date formats.
+    fn parse_date_time(&self, s: &str) -> Option<f64> {
+        self.date_formats.iter().find_map(|format| {
+            let datetime = NaiveDateTime::parse_from_str(s, format).ok();
+            let datetime = datetime.or_else(|| {
+                let date = NaiveDate::parse_from_str(s, format).ok()?;
+                date.and_hms_opt(0, 0, 0)
+            })?;
+            Some(DateSystem::V1900.to_serial(datetime))
+        })
+    }
+}
+
+/// Returns `true` if `s` consists of digits, an optional sign, decimal point and exponent only,
+/// which excludes e.g. `inf` and `NaN` that `f64` would parse.
+pub(crate) fn is_number(s: &str) -> bool {
+    s.bytes().any(|b| b.is_ascii_digit())
+        && s.bytes().all(|b| {
+            b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E')
+        })
+}
+
+/// Returns `true` if the integral part of the number `s` has a leading zero, e.g. `007`.
+fn has_leading_zero(s: &str) -> bool {
+    let digits = s.trim_start_matches(['+', '-']).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
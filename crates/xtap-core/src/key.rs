@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use calamine::{CellErrorType, DataType};
+
+use crate::{Field, Infer, Record};
+
+/// A field compared by its logical value rather than by how it is stored, so that fields of csv
+/// and spreadsheet records can be compared with each other.
+///
+/// Keys are created by [`Field::key`]. `Field::Bytes` and `Field::Str` of the same text are
+/// equal, as are numbers of the same value such as `Int(12)` and `Float(12.0)`. If rules of
+/// type inference are given, text is inferred as a typed value first, so `Bytes(b"12")` is equal
+/// to `Int(12)` as well.
+///
+/// Keys are totally ordered. Fields of different kinds are ordered like Excel sorts them:
+/// numbers, date-times, text, booleans, errors and empty fields. Floats are ordered by
+/// `f64::total_cmp`, and `NaN` is equal to itself.
+///
+/// # Examples
+///
+/// ```
+/// use xtap_core::{Field, Infer};
+///
+/// let infer = Infer::new();
+/// assert_eq!(Field::Bytes(b"12").key(None), Field::Str("12").key(None));
+/// assert_ne!(Field::Bytes(b"12").key(None), Field::Int(12).key(None));
+/// assert_eq!(Field::Bytes(b"12").key(Some(&infer)), Field::Int(12).key(None));
+/// assert_eq!(Field::Int(12).key(None), Field::Float(12.0).key(None));
+/// assert!(Field::Int(12).key(None) < Field::Str("abc").key(None));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FieldKey<'r>(Key<'r>);
+
+#[derive(Debug, Clone)]
+enum Key<'r> {
+    /// A number without a fractional part that fits in an `i64`.
+    Int(i64),
+    /// Any other number.
+    Float(f64),
+    DateTime(f64),
+    Text(Cow<'r, str>),
+    Bool(bool),
+    Error(u8),
+    Empty,
+}
+
+impl<'r> FieldKey<'r> {
+    pub(crate) fn new(
+        field: &Field<'r>,
+        infer: Option<&Infer>,
+    ) -> FieldKey<'r> {
+        let key = match *field {
+            Field::Bytes(bytes) => text_key(bytes, infer),
+            Field::Str(s) => text_key(s.as_bytes(), infer),
+            Field::Int(int) => Key::Int(int),
+            Field::Float(float) => number_key(float),
+            Field::Bool(boolean) => Key::Bool(boolean),
+            Field::DateTime(serial) => Key::DateTime(canonical(serial)),
+            Field::Error(err) => Key::Error(error_code(err)),
+            Field::Empty => Key::Empty,
+        };
+        FieldKey(key)
+    }
+
+    /// Returns the rank of the kind of this key in the ordering.
+    fn rank(&self) -> u8 {
+        match self.0 {
+            Key::Int(_) | Key::Float(_) => 0,
+            Key::DateTime(_) => 1,
+            Key::Text(_) => 2,
+            Key::Bool(_) => 3,
+            Key::Error(_) => 4,
+            Key::Empty => 5,
+        }
+    }
+}
+
+/// Returns the key of the text `bytes`, which is inferred with `infer` if given.
+fn text_key<'r>(bytes: &'r [u8], infer: Option<&Infer>) -> Key<'r> {
+    let text = || Key::Text(String::from_utf8_lossy(bytes));
+    match infer.map(|infer| infer.infer(bytes)) {
+        Some(DataType::Int(int)) => Key::Int(int),
+        Some(DataType::Float(float)) => number_key(float),
+        Some(DataType::Bool(boolean)) => Key::Bool(boolean),
+        Some(DataType::DateTime(serial)) => Key::DateTime(canonical(serial)),
+        Some(DataType::Empty) => Key::Empty,
+        _ => text(),
+    }
+}
+
+/// Returns the key of the number `float`, which is an integer if it has no fractional part.
+fn number_key<'r>(float: f64) -> Key<'r> {
+    let fits = float >= i64::MIN as f64 && float < i64::MAX as f64;
+    if float.fract() == 0.0 && fits {
+        Key::Int(float as i64)
+    } else {
+        Key::Float(canonical(float))
+    }
+}
+
+/// Returns `float` with `-0.0` turned into `0.0` and every `NaN` into the same `NaN`, so that
+/// floats equal by value have the same bits.
+fn canonical(float: f64) -> f64 {
+    if float.is_nan() {
+        f64::NAN
+    } else if float == 0.0 {
+        0.0
+    } else {
+        float
+    }
+}
+
+/// Returns the code of `err` as returned by the `ERROR.TYPE` function of Excel.
+fn error_code(err: &CellErrorType) -> u8 {
+    match err {
+        CellErrorType::Null => 1,
+        CellErrorType::Div0 => 2,
+        CellErrorType::Value => 3,
+        CellErrorType::Ref => 4,
+        CellErrorType::Name => 5,
+        CellErrorType::Num => 6,
+        CellErrorType::NA => 7,
+        CellErrorType::GettingData => 8,
+    }
+}
+
+impl PartialEq for FieldKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FieldKey<'_> {}
+
+impl PartialOrd for FieldKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FieldKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.0, &other.0) {
+            (Key::Int(a), Key::Int(b)) => a.cmp(b),
+            // an integer is never equal to a float key, which has a fractional part or is out of
+            // the range of `i64`, so ties from rounding are broken by putting integers first.
+            (Key::Int(a), Key::Float(b)) => {
+                (*a as f64).total_cmp(b).then(Ordering::Less)
+            }
+            (Key::Float(a), Key::Int(b)) => {
+                a.total_cmp(&(*b as f64)).then(Ordering::Greater)
+            }
+            (Key::Float(a), Key::Float(b)) => a.total_cmp(b),
+            (Key::DateTime(a), Key::DateTime(b)) => a.total_cmp(b),
+            (Key::Text(a), Key::Text(b)) => a.cmp(b),
+            (Key::Bool(a), Key::Bool(b)) => a.cmp(b),
+            (Key::Error(a), Key::Error(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Hash for FieldKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self.0 {
+            Key::Int(int) => int.hash(state),
+            Key::Float(float) | Key::DateTime(float) => {
+                float.to_bits().hash(state)
+            }
+            Key::Text(ref text) => text.hash(state),
+            Key::Bool(boolean) => boolean.hash(state),
+            Key::Error(code) => code.hash(state),
+            Key::Empty => {}
+        }
+    }
+}
+
+/// A record compared by the logical values of its fields, see [`FieldKey`].
+///
+/// Keys are created by [`Record::key`] and compared field by field, so a shorter record is less
+/// than a longer one it is a prefix of. Where records were read from is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+///
+/// use xtap_core::{Field, Infer, Record};
+///
+/// let csv = Record::from_iter(vec!["apple", "3"]);
+/// let xlsx = Record::from_iter(vec![Field::Str("apple"), Field::Float(3.0)]);
+///
+/// let infer = Infer::new();
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(csv.key(Some(&infer))));
+/// assert!(!seen.insert(xlsx.key(Some(&infer))));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordKey<'r>(Vec<FieldKey<'r>>);
+
+impl<'r> RecordKey<'r> {
+    pub(crate) fn new(
+        record: &'r Record,
+        infer: Option<&Infer>,
+    ) -> RecordKey<'r> {
+        RecordKey(record.iter().map(|field| field.key(infer)).collect())
+    }
+
+    /// Returns the keys of the fields of the record.
+    pub fn fields(&self) -> &[FieldKey<'r>] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn key(field: Field<'_>) -> FieldKey<'_> {
+        field.key(None)
+    }
+
+    fn hash(key: &FieldKey<'_>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Asserts that `a` and `b` are equal and have the same hash.
+    fn assert_same(a: FieldKey<'_>, b: FieldKey<'_>) {
+        assert_eq!(a, b);
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(hash(&a), hash(&b), "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn hashes_equal_numbers_alike() {
+        assert_same(key(Field::Int(12)), key(Field::Float(12.0)));
+        assert_same(key(Field::Int(0)), key(Field::Float(-0.0)));
+        assert_same(key(Field::Float(0.5)), key(Field::Float(0.5)));
+        assert_same(key(Field::Float(f64::NAN)), key(Field::Float(-f64::NAN)));
+        assert_same(key(Field::Float(1e19)), key(Field::Float(1e19)));
+        assert_same(key(Field::DateTime(0.0)), key(Field::DateTime(-0.0)));
+        assert_same(
+            Field::Bytes(b"12").key(Some(&Infer::new())),
+            key(Field::Float(12.0)),
+        );
+        assert_same(key(Field::Bytes(b"12")), key(Field::Str("12")));
+
+        assert_ne!(key(Field::Int(12)), key(Field::Float(12.5)));
+        assert_ne!(key(Field::Int(12)), key(Field::DateTime(12.0)));
+        assert_ne!(key(Field::Int(12)), key(Field::Bytes(b"12")));
+    }
+
+    #[test]
+    fn orders_keys_like_excel() {
+        let ordered = [
+            key(Field::Float(f64::NEG_INFINITY)),
+            key(Field::Int(i64::MIN)),
+            key(Field::Int(-1)),
+            key(Field::Float(-0.5)),
+            key(Field::Int(0)),
+            key(Field::Float(0.5)),
+            key(Field::Int(1)),
+            key(Field::Int(i64::MAX)),
+            key(Field::Float(9_223_372_036_854_775_808.0)),
+            key(Field::Float(f64::INFINITY)),
+            key(Field::Float(f64::NAN)),
+            key(Field::DateTime(0.0)),
+            key(Field::DateTime(44197.5)),
+            key(Field::Str("")),
+            key(Field::Str("a")),
+            key(Field::Bytes(b"b")),
+            key(Field::Bool(false)),
+            key(Field::Bool(true)),
+            key(Field::Error(&CellErrorType::Null)),
+            key(Field::Error(&CellErrorType::NA)),
+            key(Field::Empty),
+        ];
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(a.cmp(b), i.cmp(&j), "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn orders_records_field_by_field() {
+        let short = Record::from_iter(vec!["a"]);
+        let long = Record::from_iter(vec![Field::Str("a"), Field::Int(1)]);
+        let other = Record::from_iter(vec!["b"]);
+        assert!(short.key(None) < long.key(None));
+        assert!(long.key(None) < other.key(None));
+        assert_eq!(long.key(None).fields()[1], key(Field::Float(1.0)));
+    }
+}
@@ -1,17 +1,39 @@
+mod align;
+mod date;
+mod de;
 mod drop;
-mod merger;
-mod reader;
+mod headers;
+mod infer;
+mod key;
+pub mod merger;
+pub mod reader;
 mod record;
+mod ser;
+mod spill;
+mod transcode;
+mod writer;
+mod xlsx;
 
+pub use crate::date::{DateFormat, DateSystem};
+pub use crate::de::{naive_date, naive_time, DeserializeError};
 pub use crate::drop::Drop;
-pub use crate::merger::{Merger, MergerBuilder};
-pub use crate::record::{Field, Record, RecordIter};
+pub use crate::headers::Headers;
+pub use crate::infer::Infer;
+pub use crate::key::{FieldKey, RecordKey};
+pub use crate::merger::{Merger, MergerBuilder, RecordsIntoIter};
+pub use crate::reader::{
+    DeserializeRecordsIter, Reader, ReaderBuilder, RecordsIter,
+};
+pub use crate::record::{Field, FieldBuf, Record, RecordIter};
+pub use crate::transcode::{Encoding, OutputEncoding};
+pub use crate::writer::{Writer, WriterBuilder};
 
 /// The whitespace preservation behaviour.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum Trim {
     /// Preserves fields and headers.
+    #[default]
     None,
     /// Trim whitespace from headers.
     Headers,
@@ -30,9 +52,3 @@ impl Trim {
         self == &Trim::Headers || self == &Trim::All
     }
 }
-
-impl Default for Trim {
-    fn default() -> Self {
-        Trim::None
-    }
-}
@@ -1,19 +1,39 @@
-use crate::{Record, Trim};
+use calamine::DataType;
+use csv::ByteRecord;
+
+use crate::align::{Alignment, Matcher};
+pub use crate::align::{Normalize, Schema};
+use crate::reader::{self, Sheets};
+use crate::spill::{Spill, SpillReader};
+use crate::transcode::{DecodeReader, EncodeWriter};
+use crate::writer::{CsvSink, Sink};
+use crate::xlsx::XlsxSink;
+use crate::{
+    DateFormat, Drop, Encoding, Field, Infer, OutputEncoding, Reader,
+    ReaderBuilder, Record, Trim,
+};
+use std::collections::VecDeque;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
-use std::io::Result;
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{cmp, mem, vec};
 
 /// A xlsx/csv file merger.
 #[derive(Debug)]
 pub struct Merger<R> {
     /// The files to merge.
     sources: Vec<R>,
+    /// The paths of the sources, which is empty if the sources are readers.
+    paths: Vec<PathBuf>,
+    /// Rewinds a source to its start, if the sources are seekable.
+    rewind: Option<fn(&mut R) -> Result<()>>,
     /// The tracking state.
     state: MergerState,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct MergerState {
     /// This contains the headers of any xlsx/csv data, if any.
     ///
@@ -22,30 +42,173 @@ struct MergerState {
     headers: Option<Record>,
     /// Indicates whether the `sources` contain headers.
     has_headers: bool,
-    /// The maximum number of fields found.
-    max_field_count: Option<u32>,
-    /// The number of fields in the first record in each source.
-    first_field_count: Option<u32>,
+    /// The maximum number of fields in body rows, which is known after scanning all sources.
+    max_field_count: Option<usize>,
     /// Various skip options.
     skip: Option<Skip>,
     /// Newline style.
     newline: Newline,
-    /// Indicates whether the presence of ending newline in each source should be forced.
+    /// Indicates whether the presence of ending newline at the end of the output should be
+    /// forced.
     force_ending_newline: bool,
     /// Capacity of the `rdr`.
     capacity: usize,
+    /// The worksheets to read from spreadsheet sources.
+    sheets: Sheets,
+    /// The whitespace trim behaviour.
+    trim: Trim,
+    /// Whether Unicode whitespace is trimmed rather than only ASCII whitespace.
+    trim_unicode: bool,
+    /// The rules of inferring the types of csv fields in body rows, if any.
+    infer: Option<Infer>,
+    /// The encoding of csv sources.
+    encoding: Encoding,
+    /// The encoding of csv output.
+    output_encoding: OutputEncoding,
+    /// The format of date-time fields in csv output.
+    date_format: DateFormat,
+    /// The output columns when aligning columns by header names, if any.
+    schema: Option<Schema>,
+    /// The rules of matching header names when aligning columns.
+    matcher: Matcher,
+    /// The provenance columns added to each record.
+    provenance: Vec<Provenance>,
+    /// Whether the provenance columns are appended rather than prepended.
+    append_provenance: bool,
+    /// The (zero-based) columns removed from each record.
+    drop_columns: Option<Vec<usize>>,
 }
 
 impl Default for MergerState {
     fn default() -> MergerState {
-        MergerState {
-            capacity: 8 * (1 << 10),
-            has_headers: true,
-            ..Default::default()
+        MergerBuilder::new().state()
+    }
+}
+
+impl MergerState {
+    /// Classifies the `row`th (zero-based) row of the `index`th source.
+    fn classify(&self, row: usize, index: usize) -> Row {
+        let (head, trailing_only) = match self.skip {
+            Some(ref skip) => skip.head,
+            None => (0, false),
+        };
+
+        if row < head {
+            if index == 0 && trailing_only {
+                Row::Preserved
+            } else {
+                Row::Skipped
+            }
+        } else if self.has_headers && row == head {
+            Row::Header
+        } else {
+            Row::Body
+        }
+    }
+
+    /// Opens a reader over the given source.
+    fn reader<R: Read>(&self, source: R) -> Result<Reader<R>> {
+        ReaderBuilder::new()
+            .buffer_capacity(self.capacity)
+            .sheets(self.sheets.clone())
+            .encoding(self.encoding)
+            .from_reader(source)
+    }
+
+    /// Writes `line` into `wtr`, replacing its line ending with the configured newline style.
+    ///
+    /// Returns `false` if `line` has no line ending, in which case a newline must be written
+    /// before any following line.
+    fn write_line<W: Write>(&self, wtr: &mut W, line: &[u8]) -> Result<bool> {
+        let (content, terminated) = match line.strip_suffix(b"\n") {
+            Some(content) => {
+                (content.strip_suffix(b"\r").unwrap_or(content), true)
+            }
+            None => (line, false),
+        };
+
+        wtr.write_all(content)?;
+        if terminated {
+            wtr.write_all(self.newline.as_bytes())?;
+        }
+        Ok(terminated)
+    }
+
+    /// Returns `true` if the `row`th (zero-based) row of the `index`th source should be written.
+    ///
+    /// The length rules are checked against `len`, the number of fields the row had in its
+    /// source, since aligned records all have the length of the schema.
+    ///
+    /// The first header row encountered is stored in `self.headers`.
+    fn should_write(
+        &mut self,
+        record: &Record,
+        len: usize,
+        row: usize,
+        index: usize,
+    ) -> bool {
+        match self.classify(row, index) {
+            Row::Skipped => false,
+            Row::Preserved => true,
+            Row::Header => {
+                if self.headers.is_some() {
+                    return false;
+                }
+                self.headers = Some(record.clone());
+                true
+            }
+            Row::Body => match self.skip {
+                Some(ref skip) => {
+                    let not_max = skip.non_max_length
+                        && self.max_field_count.is_some_and(|max| len != max);
+                    !not_max && !skip.should_skip(record, len)
+                }
+                None => true,
+            },
         }
     }
 }
 
+/// A synthetic column telling where a merged record came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provenance {
+    /// The file name of the source, or its 1-based index if the sources are readers.
+    FileName,
+    /// The path of the source as given, or its 1-based index if the sources are readers.
+    Path,
+    /// The name of the worksheet, which is empty for csv sources.
+    Sheet,
+    /// The 1-based line number in a csv source, or the 1-based row number in a worksheet.
+    ///
+    /// A csv record spanning multiple lines is numbered by its first line.
+    Row,
+}
+
+impl Provenance {
+    /// Returns the name of this column in the header row.
+    fn header(&self) -> &'static str {
+        match self {
+            Provenance::FileName => "file",
+            Provenance::Path => "path",
+            Provenance::Sheet => "sheet",
+            Provenance::Row => "row",
+        }
+    }
+}
+
+/// The kind of a row in a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    /// A head row that should be skipped.
+    Skipped,
+    /// A head row that should be preserved.
+    Preserved,
+    /// The header row.
+    Header,
+    /// Any other row.
+    Body,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Skip {
     /// Skips a number of rows from the head of each source. The second field of this tuple
@@ -64,6 +227,29 @@ pub struct Skip {
     fields_is_empty: Option<Vec<usize>>,
 }
 
+impl Skip {
+    /// Returns `true` if the given record, which had `len` fields in its source, should be
+    /// skipped.
+    ///
+    /// Note that this only checks options that can be determined from the record itself.
+    fn should_skip(&self, record: &Record, len: usize) -> bool {
+        if let Some(threshold) = self.length_less_than {
+            if len < threshold {
+                return true;
+            }
+        }
+        if let Some(ref indexes) = self.fields_is_empty {
+            if indexes
+                .iter()
+                .any(|&i| record.get(i).is_none_or(|f| f.is_empty()))
+            {
+                return true;
+            }
+        }
+        false
+    }
+}
+
 /// The style of a newline, either unix-style `\n` or dos-style `\r\n`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Newline {
@@ -73,12 +259,34 @@ pub enum Newline {
     Crlf,
 }
 
+impl Newline {
+    /// Returns the bytes of this newline.
+    fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Newline::Lf => b"\n",
+            Newline::Crlf => b"\r\n",
+        }
+    }
+}
+
+impl Default for Newline {
+    #[cfg(windows)]
+    fn default() -> Newline {
+        Newline::Crlf
+    }
+
+    #[cfg(not(windows))]
+    fn default() -> Newline {
+        Newline::Lf
+    }
+}
+
 impl<R: Read> Merger<R> {
     /// Creates a new merger with default configuration for the given readers.
     ///
     /// To build a custom merger, use `MergerBuilder`.
     pub fn from_readers(readers: Vec<R>) -> Merger<R> {
-        Merger { sources: readers, state: Default::default() }
+        MergerBuilder::new().from_readers(readers)
     }
 
     /// Returns a new [`MergerBuilder`] for configuring a custom merger.
@@ -90,32 +298,717 @@ impl<R: Read> Merger<R> {
     ///
     /// Note that the given `wtr` is buffered automatically, so you should not wrap `wtr` in a
     /// buffered writer like `io::BufWriter`.
-    pub fn into_writer<W: Write>(self, wtr: W, format: Format) -> Result<()> {
-        todo!()
+    ///
+    /// The sources are read record by record, so the memory usage doesn't grow with the size of
+    /// the sources. The only exception is `Format::Xlsx`, which keeps the compressed workbook in
+    /// memory until all records have been written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::MergerBuilder;
+    ///
+    /// let readers = vec!["a,b\n1,2\n".as_bytes(), "a,b\n3,4\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)
+    ///     .unwrap();
+    /// assert_eq!(output, b"a,b\n1,2\n3,4\n");
+    /// ```
+    pub fn into_writer<W: Write>(
+        mut self,
+        wtr: W,
+        format: Format,
+    ) -> Result<()> {
+        match format {
+            Format::Csv => {
+                // inferred fields keep their original text, except date-times which are
+                // reformatted if a date format is set.
+                let infer = self.state.infer.take();
+                if self.state.date_format != DateFormat::Serial {
+                    self.state.infer = infer.and_then(Infer::dates_only);
+                }
+                let wtr = EncodeWriter::new(wtr, self.state.output_encoding)?;
+                let sink = CsvSink::new(
+                    wtr,
+                    self.state.newline,
+                    self.state.date_format.clone(),
+                );
+                self.write_into(sink)
+            }
+            Format::Xlsx => self.write_into(XlsxSink::new(wtr)?),
+            Format::Bytes => {
+                let wtr = EncodeWriter::new(wtr, self.state.output_encoding)?;
+                self.concat_into(wtr)
+            }
+        }
     }
 
     /// Merges the contents of the underlying readers into the given file path.
     ///
-    /// This function will overwrite the contents of the given file path.
+    /// The output format is `Format::Xlsx` if the given path has a `xlsx` extension, and
+    /// `Format::Csv` otherwise.
+    ///
+    /// This function will create the given file path if it does not exist, and will overwrite its
+    /// contents if it does.
     pub fn into_path<P: AsRef<Path>>(self, path: P) -> Result<()> {
-        let mut file =
-            OpenOptions::new().write(true).truncate(true).open(path)?;
-        todo!()
+        let format = match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("xlsx") => Format::Xlsx,
+            _ => Format::Csv,
+        };
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        self.into_writer(file, format)
+    }
+
+    /// Returns an owned iterator over the merged records, for consuming them in code rather than
+    /// writing them into a file.
+    ///
+    /// The records are the same as those written by [`Merger::into_writer`] in `Format::Xlsx`,
+    /// i.e. the header row comes first if there is one, and every skip, trim, infer, alignment,
+    /// drop and provenance option is applied. Only the output options such as the newline style,
+    /// output encoding and date format have no effect. Each record tells where
+    /// it came from, see [`Record::source`] and [`Record::row`].
+    ///
+    /// The sources are read lazily as the iterator advances, so any error opening or scanning
+    /// them is yielded by the first call to `next`. The iterator ends after yielding an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::{Field, MergerBuilder, Trim};
+    ///
+    /// let readers = vec!["a,b\n1, 2\n".as_bytes(), "a,b\n3,4\n".as_bytes()];
+    ///
+    /// let mut records = MergerBuilder::new()
+    ///     .trim(Trim::All)
+    ///     .from_readers(readers)
+    ///     .records();
+    /// let headers = records.next().unwrap()?;
+    /// assert_eq!(headers.get(0), Some(Field::Bytes(b"a")));
+    ///
+    /// let mut sum = 0;
+    /// for record in records {
+    ///     let record = record?;
+    ///     sum += record.get(1).and_then(|field| field.as_i64()).unwrap_or(0);
+    /// }
+    /// assert_eq!(sum, 6);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn records(self) -> RecordsIntoIter<R> {
+        RecordsIntoIter { state: IterState::Pending(Box::new(self)) }
+    }
+
+    /// Writes all merged records into the given sink.
+    fn write_into<S: Sink>(self, mut sink: S) -> Result<()> {
+        let mut records = Records::new(self)?;
+        let mut record = Record::default();
+        while records.read_record(&mut record)? {
+            sink.write_record(&record)?;
+        }
+        sink.finish()
+    }
+
+    /// Concatenates the sources line by line into `wtr` without parsing any field.
+    ///
+    /// Only the head, tail, header, newline and encoding options are applied.
+    fn concat_into<W: Write>(self, wtr: W) -> Result<()> {
+        let Merger { sources, state, .. } = self;
+        if state.schema.is_some() {
+            return Err(bytes_unsupported("align_by_headers"));
+        }
+        if !state.provenance.is_empty() {
+            return Err(bytes_unsupported("provenance"));
+        }
+        if state.drop_columns.is_some() {
+            return Err(bytes_unsupported("drop_columns"));
+        }
+        if state.trim != Trim::None {
+            return Err(bytes_unsupported("trim"));
+        }
+        if state.infer.is_some() {
+            return Err(bytes_unsupported("infer"));
+        }
+        let (tail, leading_only) = match state.skip {
+            Some(ref skip) if skip.non_max_length => {
+                return Err(bytes_unsupported("skip_non_max_length"))
+            }
+            Some(ref skip) if skip.length_less_than.is_some() => {
+                return Err(bytes_unsupported("skip_length_less_than"))
+            }
+            Some(ref skip) if skip.fields_is_empty.is_some() => {
+                return Err(bytes_unsupported("skip_fields_empty"))
+            }
+            Some(ref skip) => skip.tail,
+            None => (0, false),
+        };
+
+        let mut wtr = BufWriter::with_capacity(state.capacity, wtr);
+        let mut headers_written = false;
+        // Whether the last line written has a line ending.
+        let mut terminated = true;
+        // The lines that have been read but may belong to the tail of the current source.
+        let mut pending: VecDeque<(usize, Vec<u8>)> = VecDeque::new();
+        let mut spare = Vec::new();
+        let count = sources.len();
+
+        // every source is checked before writing anything, so a source that isn't csv doesn't
+        // leave a partial output behind.
+        let mut rdrs = Vec::with_capacity(count);
+        for (index, source) in sources.into_iter().enumerate() {
+            let mut rdr = BufReader::with_capacity(state.capacity, source);
+            let fmt = reader::Format::detect(rdr.fill_buf()?);
+            if fmt != reader::Format::Csv {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "source {} is not csv data but {:?}, which `Format::Bytes` cannot concatenate",
+                        index + 1,
+                        fmt
+                    ),
+                ));
+            }
+            rdrs.push(rdr);
+        }
+
+        for (index, rdr) in rdrs.into_iter().enumerate() {
+            let tail =
+                if index + 1 == count && leading_only { 0 } else { tail };
+            let mut rdr = DecodeReader::new(rdr, state.encoding)?;
+
+            for row in 0.. {
+                let mut line = mem::take(&mut spare);
+                line.clear();
+                if rdr.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                pending.push_back((row, line));
+                if pending.len() <= tail {
+                    continue;
+                }
+
+                let (row, line) = pending.pop_front().unwrap();
+                let write = match state.classify(row, index) {
+                    Row::Skipped => false,
+                    Row::Header => !mem::replace(&mut headers_written, true),
+                    Row::Preserved | Row::Body => true,
+                };
+                if write {
+                    if !terminated {
+                        wtr.write_all(state.newline.as_bytes())?;
+                    }
+                    terminated = state.write_line(&mut wtr, &line)?;
+                }
+                spare = line;
+            }
+            pending.clear();
+        }
+
+        if !terminated && state.force_ending_newline {
+            wtr.write_all(state.newline.as_bytes())?;
+        }
+        wtr.flush()
+    }
+}
+
+/// An owned iterator over the merged records of a [`Merger`].
+///
+/// This is created by [`Merger::records`], and each item yielded by it is a `io::Result<Record>`.
+pub struct RecordsIntoIter<R> {
+    state: IterState<R>,
+}
+
+enum IterState<R> {
+    /// The sources haven't been opened yet.
+    Pending(Box<Merger<R>>),
+    /// Records are being read from the sources.
+    Reading(Box<Records<R>>),
+    /// All records have been read, or an error has occurred.
+    Done,
+}
+
+impl<R: Read> Iterator for RecordsIntoIter<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        let mut records = match mem::replace(&mut self.state, IterState::Done)
+        {
+            IterState::Pending(merger) => match Records::new(*merger) {
+                Ok(records) => Box::new(records),
+                Err(err) => return Some(Err(err)),
+            },
+            IterState::Reading(records) => records,
+            IterState::Done => return None,
+        };
+
+        let mut record = Record::default();
+        match records.read_record(&mut record) {
+            Ok(true) => {
+                self.state = IterState::Reading(records);
+                Some(Ok(record))
+            }
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// A streaming reader over the merged records of all sources.
+struct Records<R> {
+    /// The sources that haven't been read yet.
+    sources: vec::IntoIter<R>,
+    /// The paths of the sources, which is empty if the sources are readers.
+    paths: Vec<Arc<Path>>,
+    /// The number of sources opened so far.
+    opened_count: usize,
+    /// The tracking state.
+    state: MergerState,
+    /// The readers of the sources that have been opened in advance.
+    opened: VecDeque<Reader<R>>,
+    /// The reader of the source being read, if any.
+    rdr: Option<Reader<R>>,
+    /// The number of parts read so far, where each source or each worksheet of a spreadsheet
+    /// source is a part.
+    parts: usize,
+    /// The worksheet being read, if any.
+    sheet: Option<String>,
+    /// The number of rows read from the current part.
+    row: usize,
+    /// The column alignment, if any.
+    alignment: Option<Alignment>,
+    /// A spare csv record that csv records are rebuilt into when removing or adding columns.
+    buf: ByteRecord,
+    /// The number of fields in the last body row read before removing or adding any column, or
+    /// `None` if the last row wasn't a body row.
+    last_len: Option<usize>,
+    /// The maximum number of fields in body rows read so far.
+    longest: usize,
+    /// The records spilled in a scanning pass, if any.
+    spill: Option<SpillReader>,
+    /// The last records read from the current part, which are held back until it's known that
+    /// they don't belong to the skipped tail.
+    tail: VecDeque<(Record, Origin)>,
+    /// Whether all sources have been exhausted.
+    exhausted: bool,
+}
+
+/// Where a record was read from.
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    /// The one-based index of the source.
+    source: usize,
+    /// The one-based index of the part.
+    part: usize,
+    /// The zero-based index of the row in the part.
+    row: usize,
+}
+
+impl<R: Read> Records<R> {
+    fn new(merger: Merger<R>) -> Result<Records<R>> {
+        let non_max_length = match merger.state.skip {
+            Some(ref skip) => skip.non_max_length,
+            None => false,
+        };
+
+        if non_max_length && merger.state.max_field_count.is_none() {
+            Records::scan(merger)
+        } else {
+            Records::stream(merger)
+        }
+    }
+
+    /// Scans all sources to find the longest body row before skipping rows that aren't the
+    /// longest.
+    ///
+    /// Seekable sources are scanned in a separate pass and then rewound. Otherwise, the records
+    /// are spilled into a temporary file during the scan and read back from it afterwards, so the
+    /// memory usage doesn't grow with the size of the sources either way.
+    fn scan(mut merger: Merger<R>) -> Result<Records<R>> {
+        let mut record = Record::default();
+        match merger.rewind {
+            Some(rewind) => {
+                let sources = merger.sources.iter_mut().collect();
+                let mut records = Records::stream(Merger {
+                    sources,
+                    paths: merger.paths.clone(),
+                    rewind: None,
+                    state: merger.state.clone(),
+                })?;
+                while records.read_record(&mut record)? {}
+                let longest = records.longest;
+                drop(records);
+
+                merger.sources.iter_mut().try_for_each(rewind)?;
+                merger.state.max_field_count = Some(longest);
+                Records::stream(merger)
+            }
+            None => {
+                let mut spill = Spill::new()?;
+                let mut records = Records::stream(merger)?;
+                while records.read_record(&mut record)? {
+                    let tag = records.last_len.map_or(0, |len| len as u64 + 1);
+                    spill.write(&record, tag)?;
+                }
+
+                records.state.max_field_count = Some(records.longest);
+                records.spill = Some(spill.into_reader()?);
+                Ok(records)
+            }
+        }
+    }
+
+    /// Creates a streaming reader over the merged records of `merger`.
+    fn stream(merger: Merger<R>) -> Result<Records<R>> {
+        let Merger { sources, paths, state, .. } = merger;
+        let mut sources = sources.into_iter();
+        let mut opened = VecDeque::new();
+        let mut alignment = state
+            .schema
+            .clone()
+            .map(|schema| Alignment::new(schema, state.matcher.clone()));
+
+        if let Some(ref mut alignment) = alignment {
+            if !state.has_headers {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the `align_by_headers` option requires headers",
+                ));
+            }
+            if alignment.needs_all_headers() {
+                let head = state.skip.as_ref().map_or(0, |skip| skip.head.0);
+                let mut headers = Vec::new();
+                for (i, source) in sources.by_ref().enumerate() {
+                    let mut rdr = state.reader(source)?;
+                    headers.extend(rdr.peek(head)?.into_iter().map(
+                        |mut r| {
+                            if state.trim.should_trim_headers() {
+                                r.trim(state.trim_unicode);
+                            }
+                            r.set_source(Some(i));
+                            r
+                        },
+                    ));
+                    opened.push_back(rdr);
+                }
+                alignment.resolve(&headers)?;
+            }
+        }
+
+        Ok(Records {
+            sources,
+            paths: paths.iter().map(|path| path.as_path().into()).collect(),
+            opened_count: 0,
+            state,
+            opened,
+            rdr: None,
+            parts: 0,
+            sheet: None,
+            row: 0,
+            alignment,
+            buf: ByteRecord::new(),
+            last_len: None,
+            longest: 0,
+            spill: None,
+            tail: VecDeque::new(),
+            exhausted: false,
+        })
+    }
+
+    /// Reads the next merged record into `record`.
+    ///
+    /// Returns `false` if all sources have been exhausted.
+    fn read_record(&mut self, record: &mut Record) -> Result<bool> {
+        if let Some(ref mut spill) = self.spill {
+            let max = self.state.max_field_count;
+            while let Some(tag) = spill.read(record)? {
+                let path = record.source().and_then(|i| self.paths.get(i));
+                record.set_path(path.cloned());
+                // only body rows have non-zero tags.
+                if tag == 0 || Some(tag as usize - 1) == max {
+                    return Ok(true);
+                }
+            }
+            return Ok(false);
+        }
+
+        while let Some(origin) = self.read_untailed(record)? {
+            let index = origin.part - 1;
+            let kind = self.state.classify(origin.row, index);
+            let trim = match kind {
+                Row::Skipped => false,
+                Row::Header => self.state.trim.should_trim_headers(),
+                Row::Preserved | Row::Body => {
+                    self.state.trim.should_trim_fields()
+                }
+            };
+            if trim {
+                record.trim(self.state.trim_unicode);
+            }
+            if let (Some(ref infer), Row::Body) = (&self.state.infer, kind) {
+                infer.apply(record);
+            }
+            let len = record.len();
+            if let Some(ref mut alignment) = self.alignment {
+                match kind {
+                    Row::Header => alignment.set_headers(record)?,
+                    Row::Body => alignment.align(record),
+                    Row::Skipped | Row::Preserved => {}
+                }
+            }
+            if self.state.should_write(record, len, origin.row, index) {
+                self.last_len = match kind {
+                    Row::Body => Some(len),
+                    _ => None,
+                };
+                self.longest =
+                    cmp::max(self.longest, self.last_len.unwrap_or(0));
+                self.drop_columns(record);
+                self.add_provenance(
+                    record,
+                    origin.source,
+                    kind == Row::Header,
+                );
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads the next record that doesn't belong to the skipped tail of its part into `record`.
+    ///
+    /// The tail is skipped in a single pass by holding back the last `count` records of the
+    /// current part in a ring buffer, which are dropped once the part is exhausted.
+    fn read_untailed(
+        &mut self,
+        record: &mut Record,
+    ) -> Result<Option<Origin>> {
+        let (count, leading_only) = match self.state.skip {
+            Some(ref skip) if skip.tail.0 > 0 => skip.tail,
+            _ => return self.read_raw(record),
+        };
+
+        while !self.exhausted {
+            let origin = match self.read_raw(record)? {
+                Some(origin) => origin,
+                None => {
+                    self.exhausted = true;
+                    if !leading_only {
+                        self.tail.clear();
+                    }
+                    break;
+                }
+            };
+
+            if self.tail.front().is_some_and(|(_, o)| o.part != origin.part) {
+                // the previous part has been exhausted, so the held back records are its tail.
+                self.tail.clear();
+            }
+            if self.tail.len() < count {
+                self.tail.push_back((mem::take(record), origin));
+                continue;
+            }
+
+            // reuse the allocation of the oldest record for the newest one.
+            let (oldest, oldest_origin) = self.tail.pop_front().unwrap();
+            self.tail.push_back((mem::replace(record, oldest), origin));
+            return Ok(Some(oldest_origin));
+        }
+
+        // the tail of the last part is preserved if `leading_only` is set.
+        Ok(self.tail.pop_front().map(|(oldest, origin)| {
+            *record = oldest;
+            origin
+        }))
+    }
+
+    /// Reads the next record of the sources into `record`, without skipping or changing any row.
+    ///
+    /// Returns `None` if all sources have been exhausted.
+    fn read_raw(&mut self, record: &mut Record) -> Result<Option<Origin>> {
+        loop {
+            let rdr = match self.rdr {
+                Some(ref mut rdr) => rdr,
+                None => {
+                    let rdr = match self.opened.pop_front() {
+                        Some(rdr) => rdr,
+                        None => match self.sources.next() {
+                            Some(source) => self.state.reader(source)?,
+                            None => return Ok(None),
+                        },
+                    };
+                    self.opened_count += 1;
+                    self.parts += 1;
+                    self.sheet = None;
+                    self.row = 0;
+                    self.rdr.get_or_insert(rdr)
+                }
+            };
+
+            if !rdr.read_record(record)? {
+                self.rdr = None;
+                continue;
+            }
+
+            if record.sheet() != self.sheet.as_deref() {
+                // the first worksheet of a source has been counted as the source itself.
+                if self.sheet.is_some() {
+                    self.parts += 1;
+                    self.row = 0;
+                }
+                self.sheet = record.sheet().map(String::from);
+            }
+
+            let source = self.opened_count - 1;
+            record.set_source(Some(source));
+            record.set_path(self.paths.get(source).cloned());
+
+            let row = self.row;
+            self.row += 1;
+            return Ok(Some(Origin {
+                source: self.opened_count,
+                part: self.parts,
+                row,
+            }));
+        }
+    }
+
+    /// Removes the dropped columns from `record`.
+    fn drop_columns(&mut self, record: &mut Record) {
+        let indexes = match self.state.drop_columns {
+            Some(ref indexes) => indexes,
+            None => return,
+        };
+
+        if record.as_byte_record().is_none() {
+            let mut i = 0;
+            record.as_xlsx_record_mut().retain(|_| {
+                i += 1;
+                !indexes.contains(&(i - 1))
+            });
+            return;
+        }
+
+        let fields = record.as_byte_record_mut();
+        self.buf.clear();
+        for (i, field) in fields.iter().enumerate() {
+            if !indexes.contains(&i) {
+                self.buf.push_field(field);
+            }
+        }
+        self.buf.set_position(fields.position().cloned());
+        mem::swap(fields, &mut self.buf);
+    }
+
+    /// Adds the provenance columns to `record` of the `source`th (one-based) source, which is a
+    /// header row if `header` is `true`.
+    fn add_provenance(
+        &mut self,
+        record: &mut Record,
+        source: usize,
+        header: bool,
+    ) {
+        if self.state.provenance.is_empty() {
+            return;
+        }
+
+        let path = self.paths.get(source - 1);
+        let values: Vec<DataType> = self
+            .state
+            .provenance
+            .iter()
+            .map(|column| match column {
+                _ if header => DataType::String(column.header().to_string()),
+                Provenance::FileName => DataType::String(match path {
+                    Some(path) => path.file_name().map_or_else(
+                        || path.display().to_string(),
+                        |name| name.to_string_lossy().into_owned(),
+                    ),
+                    None => source.to_string(),
+                }),
+                Provenance::Path => DataType::String(match path {
+                    Some(path) => path.display().to_string(),
+                    None => source.to_string(),
+                }),
+                Provenance::Sheet => {
+                    DataType::String(record.sheet().unwrap_or("").to_string())
+                }
+                Provenance::Row => match record.row() {
+                    Some(row) => DataType::Int(row as i64),
+                    None => DataType::Empty,
+                },
+            })
+            .collect();
+        let append = self.state.append_provenance;
+
+        if record.as_byte_record().is_none() {
+            let cells = record.as_xlsx_record_mut();
+            if append {
+                cells.extend(values);
+            } else {
+                cells.splice(0..0, values);
+            }
+            return;
+        }
+
+        let fields = record.as_byte_record_mut();
+        let out = &mut self.buf;
+        out.clear();
+        if append {
+            fields.iter().for_each(|field| out.push_field(field));
+        }
+        for value in values.iter() {
+            out.push_field(Field::from(value).to_display_string().as_bytes());
+        }
+        if !append {
+            fields.iter().for_each(|field| out.push_field(field));
+        }
+        out.set_position(fields.position().cloned());
+        mem::swap(fields, out);
     }
 }
 
+/// Rewinds the given seekable source to its start.
+fn rewind<R: Seek>(source: &mut R) -> Result<()> {
+    source.seek(SeekFrom::Start(0)).map(|_| ())
+}
+
+/// Returns an error indicating that the given option is not supported by `Format::Bytes`.
+fn bytes_unsupported(option: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("the `{}` option is not supported by `Format::Bytes`", option),
+    )
+}
+
 impl Merger<File> {
+    /// Creates a new merger with default configuration for the given file paths.
+    ///
+    /// To build a custom merger, use `MergerBuilder`.
     pub fn from_paths<P: AsRef<Path>>(paths: Vec<P>) -> Result<Merger<File>> {
-        let files: Result<Vec<File>> =
-            paths.into_iter().map(|p| File::open(p)).collect();
-        Ok(Merger { sources: files?, state: Default::default() })
+        MergerBuilder::new().from_paths(paths)
     }
 }
 
+/// The output format of a merger.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
+    /// Writes merged records as csv.
     Csv,
+    /// Writes merged records as a xlsx workbook.
     Xlsx,
+    /// Concatenates the sources at the byte level.
+    ///
+    /// This is much faster than `Format::Csv` as no field is parsed, but rows are delimited by
+    /// line endings, so it shouldn't be used if any field contains a newline. Only the head, tail,
+    /// header, newline and encoding options are applied in this format, and every source must be
+    /// csv data.
     Bytes,
 }
 
@@ -124,30 +1017,60 @@ pub enum Format {
 pub struct MergerBuilder {
     /// The capacity of `io::BufReader`.
     capacity: usize,
+    /// The worksheets to read from spreadsheet sources.
+    sheets: Sheets,
+    /// The encoding of csv sources.
+    encoding: Encoding,
+    /// The encoding of csv output.
+    output_encoding: OutputEncoding,
+    /// The format of date-time fields in csv output.
+    date_format: DateFormat,
     /// Whether data contains headers.
     has_headers: bool,
     /// The whitespace trim behaviour.
     trim: Trim,
+    /// Whether Unicode whitespace is trimmed rather than only ASCII whitespace.
+    trim_unicode: bool,
+    /// The rules of inferring the types of csv fields in body rows, if any.
+    infer: Option<Infer>,
     /// Various skip options.
     skip: Option<Skip>,
     /// Newline style.
     newline: Newline,
-    /// Whether the presence of ending newline in each source should be forced.
+    /// Whether the presence of ending newline at the end of the output should be forced.
     force_ending_newline: bool,
+    /// The provenance columns added to each record.
+    provenance: Vec<Provenance>,
+    /// Whether the provenance columns are appended rather than prepended.
+    append_provenance: bool,
+    /// The (zero-based) columns removed from each record.
+    drop_columns: Option<Vec<usize>>,
+    /// The output columns when aligning columns by header names, if any.
+    schema: Option<Schema>,
+    /// The rules of matching header names when aligning columns.
+    matcher: Matcher,
 }
 
 impl Default for MergerBuilder {
     fn default() -> Self {
         MergerBuilder {
             capacity: 8 * (1 << 10),
+            sheets: Sheets::default(),
+            encoding: Encoding::default(),
+            output_encoding: OutputEncoding::default(),
+            date_format: DateFormat::default(),
             has_headers: true,
             trim: Trim::default(),
+            trim_unicode: false,
+            infer: None,
             skip: Default::default(),
-            #[cfg(windows)]
-            newline: Newline::Crlf,
-            #[cfg(not(windows))]
-            newline: Newline::Lf,
+            newline: Newline::default(),
             force_ending_newline: false,
+            provenance: Vec::new(),
+            append_provenance: false,
+            drop_columns: None,
+            schema: None,
+            matcher: Matcher::default(),
         }
     }
 }
@@ -179,6 +1102,156 @@ impl MergerBuilder {
         self
     }
 
+    /// Sets the worksheets to read from spreadsheet sources.
+    ///
+    /// By default, only the first worksheet of each spreadsheet is read. If multiple worksheets
+    /// are selected, each worksheet is treated as a separate source, i.e. the skip and header
+    /// options are applied to each worksheet.
+    pub fn sheets(&mut self, sheets: Sheets) -> &mut Self {
+        self.sheets = sheets;
+        self
+    }
+
+    /// Sets the character encoding of csv sources, which are transcoded to UTF-8 before merging.
+    ///
+    /// By default, csv sources are assumed to be UTF-8. Use [`Encoding::Auto`] to detect the
+    /// encoding of each source separately, which allows merging sources in different encodings.
+    /// Spreadsheet sources are not affected.
+    pub fn encoding(&mut self, encoding: Encoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets the character encoding of the output in `Format::Csv` and `Format::Bytes`.
+    ///
+    /// By default, the output is UTF-8 without a byte order mark. Use
+    /// [`OutputEncoding::Utf8Bom`] for csv files that are meant to be opened in Excel. Xlsx
+    /// output is not affected.
+    pub fn output_encoding(&mut self, encoding: OutputEncoding) -> &mut Self {
+        self.output_encoding = encoding;
+        self
+    }
+
+    /// Sets the format of date-time fields in `Format::Csv` output.
+    ///
+    /// By default, date-times are written as Excel serial numbers such as `44197.5`, while csv
+    /// fields [`inferred`](MergerBuilder::infer) as date-times keep their original text. Xlsx
+    /// output keeps them as date cells, and `Format::Bytes` output is not affected.
+    ///
+    /// # Errors
+    ///
+    /// If `format` is a custom format that can't be written, e.g. `%Y-%Q`, an error of kind
+    /// `InvalidInput` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::{DateFormat, Infer, MergerBuilder};
+    ///
+    /// let readers = vec!["day\n2021-01-31\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .infer(Infer::new())
+    ///     .date_format(DateFormat::Custom("%d/%m/%Y".to_string()))?
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)?;
+    /// assert_eq!(output, b"day\n31/01/2021\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn date_format(&mut self, format: DateFormat) -> Result<&mut Self> {
+        format.validate()?;
+        self.date_format = format;
+        Ok(self)
+    }
+
+    /// Aligns columns by header names rather than positions, writing the columns of the given
+    /// `schema` in its order.
+    ///
+    /// The header of each source (or each worksheet) determines where its fields go. Cells of
+    /// columns that are missing in a source are filled with `Field::Empty`, and fields of columns
+    /// outside the schema are dropped. Aligned records are always spreadsheet records, hence csv
+    /// fields become [`Field::Str`] with invalid UTF-8 sequences replaced.
+    ///
+    /// `Schema::Union` and `Schema::Intersection` need the headers of all sources up front, so
+    /// all sources are opened at once. This requires headers and doesn't work with
+    /// `Format::Bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline, Schema};
+    /// use xtap_core::MergerBuilder;
+    ///
+    /// let readers = vec!["a,b\n1,2\n".as_bytes(), "b,c\n3,4\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .align_by_headers(Schema::Union)
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)
+    ///     .unwrap();
+    /// assert_eq!(output, b"a,b,c\n1,2,\n,3,4\n");
+    /// ```
+    pub fn align_by_headers(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Sets the normalization applied to header names before comparing them when aligning
+    /// columns.
+    ///
+    /// By default, header names are compared exactly.
+    pub fn normalize_headers(&mut self, normalize: Normalize) -> &mut Self {
+        self.matcher.normalize = normalize;
+        self
+    }
+
+    /// Sets the maximum Levenshtein distance between the normalized names of headers that are
+    /// considered the same column when aligning columns, e.g. `1` matches `Qty` with `Qtys`.
+    ///
+    /// Exact matches always take precedence. If a header is equally close to multiple columns,
+    /// or multiple headers are equally close to a column, the match is ambiguous and reported as
+    /// an error. A header that misses a column by no more than twice the maximum distance is also
+    /// reported as an error, suggesting the closest column. The default distance is `0`, which
+    /// disables fuzzy matching.
+    pub fn header_distance(&mut self, distance: usize) -> &mut Self {
+        self.matcher.distance = distance;
+        self
+    }
+
+    /// Treats the header name `alias` as a synonym of `name` when aligning columns.
+    ///
+    /// Both names are normalized with [`normalize_headers`](MergerBuilder::normalize_headers)
+    /// before comparing them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline, Normalize, Schema};
+    /// use xtap_core::MergerBuilder;
+    ///
+    /// let readers = vec!["Order No,Qty\n1,2\n".as_bytes(), "order_no,Amount\n3,4\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .align_by_headers(Schema::First)
+    ///     .header_alias("Amount", "Qty")
+    ///     .normalize_headers(Normalize::Alphanumeric)
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)
+    ///     .unwrap();
+    /// assert_eq!(output, b"Order No,Qty\n1,2\n3,4\n");
+    /// ```
+    pub fn header_alias(&mut self, alias: &str, name: &str) -> &mut Self {
+        self.matcher.alias(alias, name);
+        self
+    }
+
     /// Whether to treat the first row as a special header row.
     ///
     /// By default, the first row is treated as a special header row, which means the header is
@@ -190,13 +1263,108 @@ impl MergerBuilder {
 
     /// Whether fields are trimmed of leading and trailing whitespace.
     ///
-    /// By default. no trimming is performed. When reading, only characters meeting the definition
-    /// of ASCII whitespace (`[\t\n\v\f\r]`) are trimmed.
+    /// By default, no trimming is performed. When reading, only characters meeting the definition
+    /// of ASCII whitespace (`[ \t\n\f\r]`) are trimmed, unless `trim_unicode` is set. Only
+    /// string cells of spreadsheet sources are trimmed.
     pub fn trim(&mut self, trim: Trim) -> &mut Self {
         self.trim = trim;
         self
     }
 
+    /// Whether any Unicode whitespace is trimmed when trimming is enabled, rather than only ASCII
+    /// whitespace.
+    ///
+    /// This covers e.g. the no-break space (U+00A0) and the ideographic space (U+3000) commonly
+    /// found in spreadsheet exports. It is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::{MergerBuilder, Trim};
+    ///
+    /// let readers = vec!["name,qty\n\u{3000}apple\u{a0},1\n".as_bytes()];
+    /// let mut out = Vec::new();
+    /// MergerBuilder::new()
+    ///     .trim(Trim::All)
+    ///     .trim_unicode(true)
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut out, Format::Csv)?;
+    /// assert_eq!(out, b"name,qty\napple,1\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn trim_unicode(&mut self, yes: bool) -> &mut Self {
+        self.trim_unicode = yes;
+        self
+    }
+
+    /// Infers the types of csv fields in body rows with the given rules, so that csv sources
+    /// produce typed fields like spreadsheet sources do.
+    ///
+    /// Header rows are kept as is. Inferred fields keep their types in `Format::Xlsx` output,
+    /// e.g. numbers are written as number cells. `Format::Csv` output keeps their original text,
+    /// except that date-times are reformatted if [`date_format`](MergerBuilder::date_format) is
+    /// set. See [`Infer`] for the rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::{Infer, MergerBuilder};
+    ///
+    /// let readers = vec!["code,qty,day\n007,1.50,2021-01-31\n".as_bytes()];
+    /// let mut out = Vec::new();
+    /// MergerBuilder::new()
+    ///     .infer(Infer::new())
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut out, Format::Csv)?;
+    /// assert_eq!(out, b"code,qty,day\n007,1.50,2021-01-31\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn infer(&mut self, infer: Infer) -> &mut Self {
+        self.infer = Some(infer);
+        self
+    }
+
+    /// Drops rows and columns according to the given rules, replacing any skip options set
+    /// before.
+    ///
+    /// Rows are dropped like the corresponding skip options, i.e. the head and tail of every
+    /// source are dropped. Columns are dropped from every row by their (zero-based) index before
+    /// any provenance column is added, and after any column alignment, in which case the index
+    /// refers to the aligned columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::{Drop, MergerBuilder};
+    ///
+    /// let readers = vec!["a,b,c\n1,2,3\n".as_bytes(), "a,b,c\n4,,6\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .drop(Drop::new().drop_columns(vec![0]).drop_fields_empty(vec![1]))
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)
+    ///     .unwrap();
+    /// assert_eq!(output, b"b,c\n2,3\n");
+    /// ```
+    pub fn drop(&mut self, drop: Drop) -> &mut Self {
+        self.skip = Some(Skip {
+            head: (drop.head, false),
+            tail: (drop.tail, false),
+            non_max_length: drop.non_max_length,
+            length_less_than: drop.length_less_than,
+            fields_is_empty: drop.fields_empty,
+        });
+        self.drop_columns = drop.columns;
+        self
+    }
+
     /// Skips a given number of rows from the head of each file. If `trailing_only` was given
     /// `true`, then the head of the the first file is preserved.
     pub fn skip_head(
@@ -219,6 +1387,9 @@ impl MergerBuilder {
 
     /// Skips a given number of rows from the tail of each file. If `leading_only` was given
     /// `true`, then the tail of the last file is preserved.
+    ///
+    /// The tail is skipped in a single pass without knowing the number of rows in advance, by
+    /// holding back the last `count` rows in memory, so it also works on streams like stdin.
     pub fn skip_tail(
         &mut self,
         count: usize,
@@ -236,6 +1407,12 @@ impl MergerBuilder {
     }
 
     /// Skips any row if its length is not the longest.
+    ///
+    /// The longest length is only known after all sources have been read, so this takes two
+    /// passes. Sources built by [`from_paths`](MergerBuilder::from_paths) and
+    /// [`from_seekable_readers`](MergerBuilder::from_seekable_readers) are scanned and then
+    /// rewound, while records of other sources are spilled into a temporary file in the first
+    /// pass.
     pub fn skip_non_max_length(&mut self, yes: bool) -> &mut Self {
         match self.skip.as_mut() {
             Some(skip) => skip.non_max_length = yes,
@@ -285,29 +1462,80 @@ impl MergerBuilder {
         self
     }
 
-    /// Whether the presense of ending newline between each file should be forced.
+    /// Whether the presense of ending newline at the end of the output should be forced.
+    ///
+    /// Csv output always terminates each record with a newline, so this only affects
+    /// `Format::Bytes`, which otherwise keeps the output unterminated if the last line written has
+    /// no line ending. A line without line ending is always terminated if another line follows
+    /// it, so the last line of a source is never joined with the first line of the next one.
     pub fn force_ending_newline(&mut self, yes: bool) -> &mut Self {
         self.force_ending_newline = yes;
         self
     }
 
+    /// Adds synthetic columns telling where each merged record came from, before the fields of
+    /// each record, or after them if `append` is `true`.
+    ///
+    /// The header row gets the names `file`, `path`, `sheet` and `row` for the respective
+    /// columns. Provenance columns are added after any column alignment, and aren't counted by
+    /// the skip options. This doesn't work with `Format::Bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline, Provenance};
+    /// use xtap_core::MergerBuilder;
+    ///
+    /// let readers = vec!["a,b\n1,2\n".as_bytes(), "a,b\n3,4\n".as_bytes()];
+    ///
+    /// let mut output = vec![];
+    /// MergerBuilder::new()
+    ///     .provenance(vec![Provenance::FileName, Provenance::Row], false)
+    ///     .newline(Newline::Lf)
+    ///     .from_readers(readers)
+    ///     .into_writer(&mut output, Format::Csv)
+    ///     .unwrap();
+    /// assert_eq!(output, b"file,row,a,b\n1,2,1,2\n2,2,3,4\n");
+    /// ```
+    pub fn provenance(
+        &mut self,
+        columns: Vec<Provenance>,
+        append: bool,
+    ) -> &mut Self {
+        self.provenance = columns;
+        self.append_provenance = append;
+        self
+    }
+
     /// Builds a [`Merger`] from this configuration that reads data from the given readers.
     ///
     /// Note that the readers are buffered automatically, so you should not wrap any reader in a
     /// buffered reader like `io::BufReader`.
     pub fn from_readers<R: Read>(&self, readers: Vec<R>) -> Merger<R> {
-        let state = MergerState {
-            headers: None,
-            has_headers: self.has_headers,
-            max_field_count: None,
-            first_field_count: None,
-            skip: self.skip.clone(),
-            capacity: self.capacity,
-            newline: self.newline,
-            force_ending_newline: self.force_ending_newline,
-        };
+        Merger {
+            sources: readers,
+            paths: Vec::new(),
+            rewind: None,
+            state: self.state(),
+        }
+    }
 
-        Merger { state, sources: readers }
+    /// Builds a [`Merger`] from this configuration that reads data from the given seekable
+    /// readers.
+    ///
+    /// This is the same as [`from_readers`](MergerBuilder::from_readers), except that options
+    /// that need to scan all sources first, e.g. `skip_non_max_length`, rewind the readers
+    /// instead of spilling records into a temporary file.
+    pub fn from_seekable_readers<R: Read + Seek>(
+        &self,
+        readers: Vec<R>,
+    ) -> Merger<R> {
+        Merger {
+            sources: readers,
+            paths: Vec::new(),
+            rewind: Some(rewind),
+            state: self.state(),
+        }
     }
 
     /// Builds a [`Merger`] from this configuration that reads data from the given file paths.
@@ -320,20 +1548,485 @@ impl MergerBuilder {
         &self,
         paths: Vec<P>,
     ) -> Result<Merger<File>> {
-        let files: Result<Vec<File>> =
-            paths.into_iter().map(|p| File::open(p)).collect();
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect();
+        let files: Result<Vec<File>> = paths.iter().map(File::open).collect();
+
+        Ok(Merger {
+            sources: files?,
+            paths,
+            rewind: Some(rewind),
+            state: self.state(),
+        })
+    }
 
-        let state = MergerState {
+    /// Returns a fresh tracking state from this configuration.
+    fn state(&self) -> MergerState {
+        MergerState {
             headers: None,
             has_headers: self.has_headers,
             max_field_count: None,
-            first_field_count: None,
             skip: self.skip.clone(),
             capacity: self.capacity,
+            sheets: self.sheets.clone(),
+            trim: self.trim,
+            trim_unicode: self.trim_unicode,
+            infer: self.infer.clone(),
+            encoding: self.encoding,
+            output_encoding: self.output_encoding,
+            date_format: self.date_format.clone(),
+            schema: self.schema.clone(),
+            matcher: self.matcher.build(),
+            provenance: self.provenance.clone(),
+            append_provenance: self.append_provenance,
+            drop_columns: self.drop_columns.clone(),
             newline: self.newline,
             force_ending_newline: self.force_ending_newline,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{FieldBuf, Writer};
+
+    /// Merges `readers` with `builder` into csv and returns the output as a string.
+    fn merge(builder: &MergerBuilder, readers: Vec<&[u8]>) -> String {
+        let mut output = vec![];
+        builder
+            .from_readers(readers)
+            .into_writer(&mut output, Format::Csv)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn streams_sources_with_small_buffers() {
+        let sources = [
+            "# first\nname,qty\napple,1\npear,2\n# end\n",
+            "# second\nname,qty\n\"long, quoted\nname\",3\n# end\n",
+            "# third\nname,qty\nplum,4\n# end\n",
+        ];
+        let readers = || sources.iter().map(|s| s.as_bytes()).collect();
+        let mut builder = MergerBuilder::new();
+        builder
+            .buffer_capacity(4)
+            .newline(Newline::Lf)
+            .skip_head(1, true)
+            .skip_tail(1, false);
+        assert_eq!(
+            merge(&builder, readers()),
+            "# first\nname,qty\napple,1\npear,2\n\"long, quoted\nname\",3\nplum,4\n"
+        );
+
+        builder.has_headers(false).skip_head(1, false);
+        assert_eq!(
+            merge(&builder, readers()),
+            "name,qty\napple,1\npear,2\nname,qty\n\"long, quoted\nname\",3\n\
+             name,qty\nplum,4\n"
+        );
+    }
+
+    #[test]
+    fn trims_headers_and_fields() {
+        let source = " a ,\u{3000}b\n 1 ,\u{a0}2\u{a0}\n";
+        let trimmed = |trim: Trim, unicode: bool| {
+            let mut builder = MergerBuilder::new();
+            builder.trim(trim).trim_unicode(unicode).newline(Newline::Lf);
+            merge(&builder, vec![source.as_bytes()])
         };
+        assert_eq!(trimmed(Trim::None, true), source);
+        assert_eq!(
+            trimmed(Trim::Headers, false),
+            "a,\u{3000}b\n 1 ,\u{a0}2\u{a0}\n"
+        );
+        assert_eq!(trimmed(Trim::Headers, true), "a,b\n 1 ,\u{a0}2\u{a0}\n");
+        assert_eq!(
+            trimmed(Trim::Fields, false),
+            " a ,\u{3000}b\n1,\u{a0}2\u{a0}\n"
+        );
+        assert_eq!(trimmed(Trim::Fields, true), " a ,\u{3000}b\n1,2\n");
+        assert_eq!(
+            trimmed(Trim::All, false),
+            "a,\u{3000}b\n1,\u{a0}2\u{a0}\n"
+        );
+        assert_eq!(trimmed(Trim::All, true), "a,b\n1,2\n");
+    }
+
+    #[test]
+    fn trims_spreadsheet_cells() {
+        let mut xlsx = vec![];
+        let mut wtr = Writer::from_writer(&mut xlsx, Format::Xlsx).unwrap();
+        wtr.serialize((" a ", "\u{3000}b")).unwrap();
+        wtr.serialize((" 1 ", "\u{a0}2\u{a0}")).unwrap();
+        wtr.finish().unwrap();
+
+        let mut builder = MergerBuilder::new();
+        builder.trim(Trim::Fields).newline(Newline::Lf);
+        assert_eq!(
+            merge(&builder, vec![xlsx.as_slice()]),
+            " a ,\u{3000}b\n1,\u{a0}2\u{a0}\n"
+        );
+        builder.trim(Trim::All).trim_unicode(true);
+        assert_eq!(merge(&builder, vec![xlsx.as_slice()]), "a,b\n1,2\n");
+    }
+
+    #[test]
+    fn trims_headers_before_aligning() {
+        let readers =
+            vec!["a,b\n1,2\n".as_bytes(), " b ,\u{a0}a\n3,4\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder
+            .align_by_headers(Schema::First)
+            .trim(Trim::Headers)
+            .trim_unicode(true)
+            .newline(Newline::Lf);
+        assert_eq!(merge(&builder, readers), "a,b\n1,2\n4,3\n");
+    }
+
+    #[test]
+    fn drops_columns() {
+        let readers =
+            vec!["a,b,c\n1,2,3\n".as_bytes(), "a,b,c\n4,5,6\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder
+            .drop(Drop::new().drop_columns(vec![2, 0, 0, 7]))
+            .newline(Newline::Lf);
+        assert_eq!(merge(&builder, readers.clone()), "b\n2\n5\n");
+
+        builder.provenance(vec![Provenance::FileName], true);
+        assert_eq!(merge(&builder, readers), "b,file\n2,1\n5,2\n");
+    }
+
+    #[test]
+    fn drops_columns_of_spreadsheets() {
+        let mut xlsx = vec![];
+        let mut wtr = Writer::from_writer(&mut xlsx, Format::Xlsx).unwrap();
+        wtr.serialize(("a", "b", "c")).unwrap();
+        wtr.serialize((1, 2, 3)).unwrap();
+        wtr.finish().unwrap();
+
+        let readers = vec![xlsx.as_slice(), "a,b,c\n4,5,6\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.drop(Drop::new().drop_columns(vec![1])).newline(Newline::Lf);
+        let records: Vec<Record> = builder
+            .from_readers(readers)
+            .records()
+            .map(|record| record.unwrap())
+            .collect();
+        let fields: Vec<Vec<FieldBuf>> = records
+            .iter()
+            .map(|record| record.iter().map(FieldBuf::from).collect())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                vec![FieldBuf::Str("a".to_string()), "c".into()],
+                vec![FieldBuf::Float(1.0), FieldBuf::Float(3.0)],
+                vec![FieldBuf::Bytes(b"4".to_vec()), b"6".to_vec().into()],
+            ]
+        );
+        assert_eq!(records[2].row(), Some(2));
+    }
+
+    #[test]
+    fn drops_columns_after_aligning() {
+        let readers = vec!["a,b\n1,2\n".as_bytes(), "b,c\n3,4\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder
+            .align_by_headers(Schema::Union)
+            .drop(Drop::new().drop_columns(vec![1]))
+            .newline(Newline::Lf);
+        assert_eq!(merge(&builder, readers), "a,c\n1,\n,4\n");
+    }
+
+    #[test]
+    fn tracks_positions_of_crlf_sources() {
+        let readers = vec![
+            "a,b\r\n1,2\r\n\r\n3,4\r\n".as_bytes(),
+            "\u{feff}a,b\r\n5,6\r\n".as_bytes(),
+        ];
+        let positions: Vec<_> = Merger::from_readers(readers)
+            .records()
+            .map(|record| record.unwrap())
+            .map(|record| (record.source(), record.row(), record.byte()))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (Some(0), Some(1), Some(0)),
+                (Some(0), Some(2), Some(5)),
+                (Some(0), Some(4), Some(12)),
+                (Some(1), Some(2), Some(5)),
+            ]
+        );
+    }
+
+    /// A source that fails with an error once `data` has been read.
+    struct Failing(&'static [u8]);
+
+    impl Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.0.is_empty() {
+                return Err(Error::other("broken source"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn iterates_over_owned_records() {
+        let readers = vec!["a,b\n1,2\n".as_bytes(), "a,b\n3,4\n".as_bytes()];
+        let mut iter = Merger::from_readers(readers).records();
+        let expected = [["a", "b"], ["1", "2"], ["3", "4"]];
+        for fields in expected.iter() {
+            let record = iter.next().unwrap().unwrap();
+            assert_eq!(record, Record::from_iter(fields.iter().copied()));
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn stops_iterating_after_an_error() {
+        // the sources are only opened by the first call to `next`.
+        let readers = vec![Failing(b"")];
+        let mut iter = Merger::from_readers(readers).records();
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "broken source");
+        assert!(iter.next().is_none());
+
+        let readers = vec![Failing(b"a,b\n1,2\n"), Failing(b"a,b\n3,4\n")];
+        let mut builder = MergerBuilder::new();
+        builder.buffer_capacity(4);
+        let results: Vec<_> =
+            builder.from_readers(readers).records().collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[1].as_ref().unwrap(),
+            &Record::from_iter(["1", "2"])
+        );
+        assert!(results[2].is_err());
+    }
+
+    /// Concatenates `readers` with `builder` into `Format::Bytes` output.
+    fn concat(builder: &MergerBuilder, readers: Vec<&[u8]>) -> Result<String> {
+        let mut output = vec![];
+        builder
+            .from_readers(readers)
+            .into_writer(&mut output, Format::Bytes)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn concatenates_bytes_dropping_repeated_headers() {
+        let readers = vec!["a,b\n1,2\n".as_bytes(), "a,b\n3,4\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.newline(Newline::Lf);
+        assert_eq!(
+            concat(&builder, readers.clone()).unwrap(),
+            "a,b\n1,2\n3,4\n"
+        );
+
+        builder.has_headers(false);
+        assert_eq!(concat(&builder, readers).unwrap(), "a,b\n1,2\na,b\n3,4\n");
+    }
+
+    #[test]
+    fn concatenates_bytes_skipping_heads() {
+        let readers =
+            vec!["# x\na,b\n1,2\n".as_bytes(), "# y\na,b\n3,4\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.newline(Newline::Lf).skip_head(1, false);
+        assert_eq!(
+            concat(&builder, readers.clone()).unwrap(),
+            "a,b\n1,2\n3,4\n"
+        );
+
+        builder.skip_head(1, true);
+        assert_eq!(concat(&builder, readers).unwrap(), "# x\na,b\n1,2\n3,4\n");
+    }
+
+    #[test]
+    fn concatenates_bytes_skipping_tails() {
+        let readers = vec![
+            "a,b\n1,2\n# x\n".as_bytes(),
+            "a,b\n# y\n".as_bytes(),
+            "a,b\n3,4\n# z\n".as_bytes(),
+        ];
+        let mut builder = MergerBuilder::new();
+        builder.newline(Newline::Lf).skip_tail(1, false);
+        assert_eq!(
+            concat(&builder, readers.clone()).unwrap(),
+            "a,b\n1,2\n3,4\n"
+        );
+
+        builder.skip_tail(1, true);
+        assert_eq!(concat(&builder, readers).unwrap(), "a,b\n1,2\n3,4\n# z\n");
+    }
+
+    #[test]
+    fn concatenates_bytes_converting_newlines() {
+        let readers = vec!["a,b\n1,2\r\n".as_bytes(), "a,b\r\n3,4".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.newline(Newline::Crlf);
+        assert_eq!(
+            concat(&builder, readers.clone()).unwrap(),
+            "a,b\r\n1,2\r\n3,4"
+        );
+
+        builder.newline(Newline::Lf);
+        assert_eq!(concat(&builder, readers).unwrap(), "a,b\n1,2\n3,4");
+    }
+
+    #[test]
+    fn concatenates_bytes_forcing_ending_newline() {
+        let readers = vec!["a,b\n1,2".as_bytes(), "a,b\n3,4".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.newline(Newline::Lf);
+        assert_eq!(
+            concat(&builder, readers.clone()).unwrap(),
+            "a,b\n1,2\n3,4"
+        );
+
+        builder.force_ending_newline(true);
+        assert_eq!(concat(&builder, readers).unwrap(), "a,b\n1,2\n3,4\n");
+
+        // the unterminated last line of a source is followed by the next written line even if
+        // the rest of its source is skipped.
+        let readers = vec!["a,b\n1,2".as_bytes(), "a,b".as_bytes()];
+        builder.force_ending_newline(false);
+        assert_eq!(concat(&builder, readers).unwrap(), "a,b\n1,2");
+    }
+
+    #[test]
+    fn rejects_non_csv_sources_in_bytes() {
+        let mut xlsx = vec![];
+        let mut wtr = Writer::from_writer(&mut xlsx, Format::Xlsx).unwrap();
+        wtr.serialize(("a", "b")).unwrap();
+        wtr.finish().unwrap();
+
+        let mut output = vec![];
+        let err = MergerBuilder::new()
+            .from_readers(vec!["a,b\n1,2\n".as_bytes(), xlsx.as_slice()])
+            .into_writer(&mut output, Format::Bytes)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "source 2 is not csv data but Xlsx, which `Format::Bytes` cannot concatenate"
+        );
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn skips_short_rows_before_aligning() {
+        let readers =
+            vec!["a,b\n1,2\n3\n".as_bytes(), "b,c\n4,5\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.align_by_headers(Schema::Union).skip_length_less_than(2);
+        assert_eq!(merge(&builder, readers), "a,b,c\n1,2,\n,4,5\n");
+    }
+
+    #[test]
+    fn skips_non_max_length_rows_before_aligning() {
+        let readers =
+            vec!["a,b\n1,2\n3\n".as_bytes(), "b,c\n4,5\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.align_by_headers(Schema::Union).skip_non_max_length(true);
+        assert_eq!(merge(&builder, readers), "a,b,c\n1,2,\n,4,5\n");
+    }
+
+    #[test]
+    fn scans_seekable_sources_by_rewinding() {
+        let sources = ["a,b\n1,2\n3\n4,5\n", "a\n6\n7,8,9\n"];
+        let mut builder = MergerBuilder::new();
+        builder.skip_non_max_length(true);
+        let readers = sources.iter().map(|s| Cursor::new(s.as_bytes()));
+        let records: Vec<_> = builder
+            .from_seekable_readers(readers.collect())
+            .records()
+            .map(|record| record.unwrap())
+            .collect();
+
+        let rows: Vec<_> = records
+            .iter()
+            .map(|record| (record.source(), record.row()))
+            .collect();
+        assert_eq!(rows, [(Some(0), Some(1)), (Some(1), Some(3))]);
+        assert_eq!(records[1], Record::from_iter(["7", "8", "9"]));
+
+        let spilled: Vec<_> = sources.iter().map(|s| s.as_bytes()).collect();
+        assert_eq!(merge(&builder, spilled), "a,b\n7,8,9\n");
+    }
+
+    #[test]
+    fn skips_tails_of_each_part() {
+        let readers = vec![
+            "1\n2\n3\n4\n".as_bytes(),
+            "5\n6\n7\n".as_bytes(),
+            "8\n9\n10\n11\n12\n".as_bytes(),
+        ];
+        let mut builder = MergerBuilder::new();
+        builder.has_headers(false).skip_tail(2, false);
+        assert_eq!(merge(&builder, readers.clone()), "1\n2\n5\n8\n9\n10\n");
+
+        builder.skip_tail(2, true);
+        assert_eq!(merge(&builder, readers), "1\n2\n5\n8\n9\n10\n11\n12\n");
+    }
+
+    #[test]
+    fn skips_tails_longer_than_a_source() {
+        let readers = vec![
+            "1\n2\n".as_bytes(),
+            "3\n4\n5\n6\n".as_bytes(),
+            "".as_bytes(),
+            "7\n".as_bytes(),
+        ];
+        let mut builder = MergerBuilder::new();
+        builder.has_headers(false).skip_tail(3, false);
+        assert_eq!(merge(&builder, readers.clone()), "3\n");
+
+        builder.skip_tail(3, true);
+        assert_eq!(merge(&builder, readers), "3\n7\n");
+    }
+
+    #[test]
+    fn keeps_text_of_inferred_fields_in_csv_output() {
+        let readers = vec!["a,b,c,d\n1.50,TRUE,1e3,2021-01-31\n".as_bytes()];
+        let mut builder = MergerBuilder::new();
+        builder.infer(Infer::new()).newline(Newline::Lf);
+        assert_eq!(
+            merge(&builder, readers.clone()),
+            "a,b,c,d\n1.50,TRUE,1e3,2021-01-31\n"
+        );
+
+        builder
+            .date_format(DateFormat::Custom("%d/%m/%Y".to_string()))
+            .unwrap();
+        assert_eq!(
+            merge(&builder, readers),
+            "a,b,c,d\n1.50,TRUE,1e3,31/01/2021\n"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_date_formats() {
+        for format in ["%Y-%Q", "%Y-%m-%d %z", "%"] {
+            let format = DateFormat::Custom(format.to_string());
+            let err = MergerBuilder::new().date_format(format.clone()).err();
+            assert_eq!(err.unwrap().kind(), ErrorKind::InvalidInput);
+            let err = crate::WriterBuilder::new().date_format(format).err();
+            assert_eq!(err.unwrap().kind(), ErrorKind::InvalidInput);
+        }
 
-        Ok(Merger { sources: files?, state })
+        let format = DateFormat::Custom("%d/%m/%Y %H:%M".to_string());
+        assert!(MergerBuilder::new().date_format(format.clone()).is_ok());
+        assert!(crate::WriterBuilder::new().date_format(format).is_ok());
     }
 }
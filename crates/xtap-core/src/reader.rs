@@ -1,18 +1,1333 @@
-use std::io;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, Cursor, Read};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use std::vec;
+
+use calamine::{DataType, Ods, Range, Reader as _, Xls, Xlsx};
+use glob::Pattern;
+use serde::de::DeserializeOwned;
+use xtap_util::closest_msg;
+
+use crate::transcode::DecodeReader;
+use crate::{DateSystem, Encoding, Infer, Record};
+
+/// The signature of a zip local file header.
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+/// The signature of an OLE2 compound file, which is the container of xls files.
+const CFB_SIGNATURE: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+/// The content of the `mimetype` entry of an ods file.
+const ODS_MIMETYPE: &[u8] = b"application/vnd.oasis.opendocument.spreadsheet";
 
 /// A xlsx/csv file reader.
+///
+/// Records are read as [`Record`]s no matter the underlying format. Csv data is read record by
+/// record, while spreadsheets are loaded into memory as a whole since their contents can only be
+/// located via the index at the end of the file.
+///
+/// To build a custom reader, use [`ReaderBuilder`].
 pub struct Reader<R> {
-    rdr: io::BufReader<R>,
+    inner: ReaderInner<R>,
     fmt: Format,
+    /// The csv records that have been peeked but not read yet.
+    peeked: VecDeque<Record>,
+    /// The rules of inferring the types of csv fields, if any.
+    infer: Option<Infer>,
+    /// The path of the file being read, if any.
+    path: Option<Arc<Path>>,
+    /// Whether the first record of csv data and of each worksheet is a header when deserializing.
+    has_headers: bool,
+}
+
+enum ReaderInner<R> {
+    Csv(csv::Reader<LineEnds<DecodeReader<io::BufReader<R>>>>),
+    Workbook(Workbook),
+}
+
+/// A spreadsheet loaded in memory.
+struct Workbook {
+    sheets: Spreadsheet,
+    /// The names of the selected worksheets that haven't been loaded yet.
+    pending: vec::IntoIter<String>,
+    /// The worksheets that have been loaded but not read yet.
+    loaded: VecDeque<Sheet>,
+    /// The worksheet being read, if any.
+    current: Option<Sheet>,
+    /// The date system of the serials of date-time cells.
+    system: DateSystem,
+}
+
+enum Spreadsheet {
+    Xlsx(Xlsx<Cursor<Vec<u8>>>),
+    Xls(Xls<Cursor<Vec<u8>>>),
+    Ods(Ods<Cursor<Vec<u8>>>),
+}
+
+/// The worksheet being read.
+struct Sheet {
+    name: Arc<str>,
+    range: Range<DataType>,
+    /// The number of empty columns before the first column of `range`.
+    offset: usize,
+    /// The zero-based index of the first row of `range` in the worksheet.
+    first_row: usize,
+    /// The number of rows read from `range`.
+    row: usize,
+    /// The date system of the serials of date-time cells.
+    system: DateSystem,
+}
+
+impl Spreadsheet {
+    /// Loads a spreadsheet of the given format from `rdr`, along with the date system it uses.
+    fn load<R: Read>(
+        mut rdr: R,
+        fmt: Format,
+    ) -> io::Result<(Spreadsheet, DateSystem)> {
+        let mut buf = Vec::new();
+        rdr.read_to_end(&mut buf)?;
+        Spreadsheet::from_buf(buf, fmt)
+    }
+
+    /// Loads a spreadsheet of the given format from `buf`, along with the date system it uses.
+    fn from_buf(
+        buf: Vec<u8>,
+        fmt: Format,
+    ) -> io::Result<(Spreadsheet, DateSystem)> {
+        let system = match fmt {
+            Format::Xlsx => xlsx_date_system(&buf),
+            _ => DateSystem::V1900,
+        };
+        let buf = Cursor::new(buf);
+
+        let sheets = match fmt {
+            Format::Xlsx => {
+                Spreadsheet::Xlsx(Xlsx::new(buf).map_err(invalid_data)?)
+            }
+            Format::Xls => {
+                Spreadsheet::Xls(Xls::new(buf).map_err(invalid_data)?)
+            }
+            Format::Ods => {
+                Spreadsheet::Ods(Ods::new(buf).map_err(invalid_data)?)
+            }
+            _ => unreachable!(),
+        };
+        Ok((sheets, system))
+    }
+
+    fn sheet_names(&self) -> &[String] {
+        match self {
+            Spreadsheet::Xlsx(sheets) => sheets.sheet_names(),
+            Spreadsheet::Xls(sheets) => sheets.sheet_names(),
+            Spreadsheet::Ods(sheets) => sheets.sheet_names(),
+        }
+    }
+
+    /// Reads the worksheet of the given name, whose date-time cells are in the date system
+    /// `system`.
+    fn worksheet(
+        &mut self,
+        name: String,
+        system: DateSystem,
+    ) -> io::Result<Sheet> {
+        let range = match self {
+            Spreadsheet::Xlsx(sheets) => sheets
+                .worksheet_range(&name)
+                .map(|range| range.map_err(invalid_data)),
+            Spreadsheet::Xls(sheets) => sheets
+                .worksheet_range(&name)
+                .map(|range| range.map_err(invalid_data)),
+            Spreadsheet::Ods(sheets) => sheets
+                .worksheet_range(&name)
+                .map(|range| range.map_err(invalid_data)),
+        };
+        let range = range.unwrap_or_else(|| Ok(Range::empty()))?;
+        let (first_row, offset) = range
+            .start()
+            .map_or((0, 0), |(row, col)| (row as usize, col as usize));
+
+        Ok(Sheet {
+            name: name.into(),
+            range,
+            offset,
+            first_row,
+            row: 0,
+            system,
+        })
+    }
+}
+
+impl Workbook {
+    fn read_record(&mut self, record: &mut Record) -> io::Result<bool> {
+        loop {
+            let sheet = match self.current {
+                Some(ref mut sheet) => sheet,
+                None => match self.loaded.pop_front() {
+                    Some(sheet) => self.current.get_or_insert(sheet),
+                    None => match self.pending.next() {
+                        Some(name) => {
+                            let sheet =
+                                self.sheets.worksheet(name, self.system)?;
+                            self.current.get_or_insert(sheet)
+                        }
+                        None => return Ok(false),
+                    },
+                },
+            };
+
+            if !sheet.get(sheet.row, record) {
+                self.current = None;
+                continue;
+            }
+            sheet.row += 1;
+            return Ok(true);
+        }
+    }
+
+    /// Returns the `row`th (zero-based) unread record of each remaining worksheet.
+    fn peek(&mut self, row: usize) -> io::Result<Vec<Record>> {
+        for name in self.pending.by_ref() {
+            let sheet = self.sheets.worksheet(name, self.system)?;
+            self.loaded.push_back(sheet);
+        }
+
+        let mut records = Vec::new();
+        for sheet in self.current.iter().chain(self.loaded.iter()) {
+            let mut record = Record::default();
+            if sheet.get(sheet.row + row, &mut record) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+impl Sheet {
+    /// Reads the `row`th (zero-based) row of this worksheet into `record`, with date-time cells
+    /// converted into the 1900 date system. Serials less than `1` are time-only values and are
+    /// kept as is, which includes date-times on `1904-01-01` in the 1904 date system since they
+    /// can't be told apart without the number format of the cell.
+    ///
+    /// Returns `false` if the row doesn't exist.
+    fn get(&self, row: usize, record: &mut Record) -> bool {
+        if row >= self.range.height() {
+            return false;
+        }
+        let cells = record.as_xlsx_record_mut();
+        cells.clear();
+        cells.resize(self.offset, DataType::Empty);
+        cells.extend_from_slice(&self.range[row]);
+        if self.system == DateSystem::V1904 {
+            for cell in cells.iter_mut() {
+                match cell {
+                    DataType::DateTime(serial) if *serial >= 1.0 => {
+                        *serial += DateSystem::OFFSET
+                    }
+                    _ => {}
+                }
+            }
+        }
+        record.set_sheet(Some(self.name.clone()));
+        record.set_row(Some((self.first_row + row + 1) as u64));
+        record.set_byte(None);
+        true
+    }
+}
+
+/// Selects the worksheets to read from a spreadsheet.
+///
+/// This has no effect on csv data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sheets {
+    /// Reads the worksheet with the given name.
+    Name(String),
+    /// Reads the worksheet at the given zero-based index.
+    Index(usize),
+    /// Reads all worksheets whose names match the given glob pattern, e.g. `2021-*`.
+    Glob(String),
+    /// Reads all worksheets.
+    All,
+}
+
+impl Default for Sheets {
+    /// Reads the first worksheet.
+    fn default() -> Sheets {
+        Sheets::Index(0)
+    }
+}
+
+impl Sheets {
+    /// Returns the selected worksheet names in `names`, in the order they appear.
+    fn select(&self, names: &[String]) -> io::Result<Vec<String>> {
+        let selected = match self {
+            Sheets::Name(name) => {
+                if !names.contains(name) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "worksheet `{}` not found{}",
+                            name,
+                            closest_msg(name, names.iter(), |n| n.as_str())
+                        ),
+                    ));
+                }
+                vec![name.clone()]
+            }
+            Sheets::Index(i) => match names.get(*i) {
+                Some(name) => vec![name.clone()],
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "worksheet index {} out of range, the spreadsheet has {} worksheets",
+                            i,
+                            names.len()
+                        ),
+                    ))
+                }
+            },
+            Sheets::Glob(pattern) => {
+                let pattern = Pattern::new(pattern).map_err(|err| {
+                    io::Error::new(io::ErrorKind::InvalidInput, err)
+                })?;
+                let selected: Vec<_> = names
+                    .iter()
+                    .filter(|name| pattern.matches(name))
+                    .cloned()
+                    .collect();
+                if selected.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no worksheet matches `{}`", pattern),
+                    ));
+                }
+                selected
+            }
+            Sheets::All => names.to_vec(),
+        };
+        Ok(selected)
+    }
+}
+
+/// A builder used for configuring a custom reader.
+#[derive(Debug, Clone)]
+pub struct ReaderBuilder {
+    /// The capacity of `io::BufReader`.
+    capacity: usize,
+    /// The worksheets to read.
+    sheets: Sheets,
+    /// The encoding of csv data.
+    encoding: Encoding,
+    /// The rules of inferring the types of csv fields, if any.
+    infer: Option<Infer>,
+    /// Whether the first record of csv data and of each worksheet is a header.
+    has_headers: bool,
+}
+
+impl Default for ReaderBuilder {
+    fn default() -> ReaderBuilder {
+        ReaderBuilder {
+            capacity: 8 * (1 << 10),
+            sheets: Sheets::default(),
+            encoding: Encoding::default(),
+            infer: None,
+            has_headers: true,
+        }
+    }
+}
+
+impl ReaderBuilder {
+    /// Creates a new reader builder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::reader::Sheets;
+    /// use xtap_core::ReaderBuilder;
+    ///
+    /// let rdr = ReaderBuilder::new()
+    ///     .sheets(Sheets::Name("Data".to_string()))
+    ///     .from_reader("a,b\n1,2\n".as_bytes())
+    ///     .unwrap();
+    /// ```
+    pub fn new() -> ReaderBuilder {
+        ReaderBuilder::default()
+    }
+
+    /// Sets the capacity (in bytes) of the buffer used in reading xlsx/csv files.
+    ///
+    /// The format is detected from the first chunk of the data, so the capacity should be large
+    /// enough to contain the leading entries of a zip archive. The default buffer capacity is 8KB
+    /// currently.
+    pub fn buffer_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the worksheets to read from a spreadsheet.
+    ///
+    /// By default, only the first worksheet is read.
+    pub fn sheets(&mut self, sheets: Sheets) -> &mut Self {
+        self.sheets = sheets;
+        self
+    }
+
+    /// Sets the character encoding of csv data, which is transcoded to UTF-8 before parsing.
+    ///
+    /// By default, csv data is assumed to be UTF-8. Use [`Encoding::Auto`] to detect the encoding
+    /// from the first chunk of the data.
+    pub fn encoding(&mut self, encoding: Encoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Infers the types of csv fields with the given rules, so that csv records have typed fields
+    /// like spreadsheet records do.
+    ///
+    /// By default, all csv fields are read as [`Field::Bytes`](crate::Field::Bytes). See
+    /// [`Infer`] for the rules.
+    pub fn infer(&mut self, infer: Infer) -> &mut Self {
+        self.infer = Some(infer);
+        self
+    }
+
+    /// Whether the first record of csv data and of each worksheet is a header, which
+    /// [`Reader::deserialize`] matches the fields of structs and maps against.
+    ///
+    /// This is `true` by default. Otherwise, every record is deserialized, and structs are
+    /// deserialized from the fields in order. [`Reader::read_record`] is not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::ReaderBuilder;
+    ///
+    /// let mut rdr = ReaderBuilder::new()
+    ///     .has_headers(false)
+    ///     .from_reader("apple,3\npear,4\n".as_bytes())?;
+    /// let rows: Vec<(String, u32)> = rdr.deserialize().collect::<Result<_, _>>()?;
+    /// assert_eq!(rows, vec![("apple".into(), 3), ("pear".into(), 4)]);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn has_headers(&mut self, yes: bool) -> &mut Self {
+        self.has_headers = yes;
+        self
+    }
+
+    /// Builds a [`Reader`] from this configuration that reads data from the given `rdr`.
+    ///
+    /// The format of the data is detected from its content rather than the file extension, see
+    /// [`Format::detect`].
+    ///
+    /// Note that `rdr` is buffered automatically, so you should not wrap `rdr` in a buffered
+    /// reader like `io::BufReader`.
+    ///
+    /// # Errors
+    ///
+    /// If the data is a zip archive other than xlsx and ods, if there was any problem loading a
+    /// spreadsheet, or if the selected worksheets don't exist, then this returns an error.
+    pub fn from_reader<R: Read>(&self, rdr: R) -> io::Result<Reader<R>> {
+        let mut rdr = io::BufReader::with_capacity(self.capacity, rdr);
+        let fmt = Format::detect(rdr.fill_buf()?);
+
+        let (fmt, sheets) = match fmt {
+            Format::Other => {
+                // the leading entries may not be walkable, e.g. if their sizes are stored after
+                // their data, so the central directory of the whole archive is read instead.
+                let mut buf = Vec::new();
+                rdr.read_to_end(&mut buf)?;
+                match detect_archive(&buf) {
+                    Format::Other => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "unsupported zip archive",
+                        ))
+                    }
+                    fmt => (fmt, Some(Spreadsheet::from_buf(buf, fmt)?)),
+                }
+            }
+            Format::Xlsx | Format::Xls | Format::Ods => {
+                (fmt, Some(Spreadsheet::load(rdr.by_ref(), fmt)?))
+            }
+            Format::Csv => (fmt, None),
+        };
+
+        let inner = match sheets {
+            None => {
+                let rdr =
+                    LineEnds::new(DecodeReader::new(rdr, self.encoding)?);
+                let rdr = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .flexible(true)
+                    .buffer_capacity(self.capacity)
+                    .from_reader(rdr);
+                ReaderInner::Csv(rdr)
+            }
+            Some((sheets, system)) => {
+                let pending = self.sheets.select(sheets.sheet_names())?;
+                ReaderInner::Workbook(Workbook {
+                    sheets,
+                    pending: pending.into_iter(),
+                    loaded: VecDeque::new(),
+                    current: None,
+                    system,
+                })
+            }
+        };
+
+        Ok(Reader {
+            inner,
+            fmt,
+            peeked: VecDeque::new(),
+            infer: self.infer.clone(),
+            path: None,
+            has_headers: self.has_headers,
+        })
+    }
+
+    /// Builds a [`Reader`] from this configuration that reads data from the given file path.
+    ///
+    /// # Errors
+    ///
+    /// If there was any problem opening the given file path or reading from it, then this returns
+    /// the corresponding error.
+    pub fn from_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> io::Result<Reader<File>> {
+        let mut rdr = self.from_reader(File::open(path.as_ref())?)?;
+        rdr.path = Some(path.as_ref().into());
+        Ok(rdr)
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader with default configuration for the given `rdr`.
+    ///
+    /// To build a custom reader, use [`ReaderBuilder`].
+    ///
+    /// # Errors
+    ///
+    /// See [`ReaderBuilder::from_reader`].
+    pub fn from_reader(rdr: R) -> io::Result<Reader<R>> {
+        ReaderBuilder::new().from_reader(rdr)
+    }
+
+    /// Returns the detected format of the underlying data.
+    pub fn format(&self) -> Format {
+        self.fmt
+    }
+
+    /// Reads a single record into the given `record`.
+    ///
+    /// Returns `false` if no more records could be read. Reusing a single record across calls
+    /// avoids allocating a new record for each row.
+    ///
+    /// Csv records are read as is, hence their fields are [`Field::Bytes`](crate::Field::Bytes),
+    /// unless [`ReaderBuilder::infer`] is set.
+    /// Spreadsheet records keep their cell types, and begin with empty fields if the worksheet
+    /// doesn't start from the first column. The records of all selected worksheets are read one
+    /// worksheet after another, and [`Record::sheet`] tells which worksheet a record came from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::{Reader, Record};
+    ///
+    /// let mut rdr = Reader::from_reader("a,b\n1,2\n".as_bytes()).unwrap();
+    /// let mut record = Record::default();
+    /// while rdr.read_record(&mut record).unwrap() {
+    ///     assert_eq!(record.len(), 2);
+    /// }
+    /// ```
+    pub fn read_record(&mut self, record: &mut Record) -> io::Result<bool> {
+        match self.inner {
+            ReaderInner::Csv(ref mut rdr) => {
+                match self.peeked.pop_front() {
+                    Some(peeked) => *record = peeked,
+                    None if read_csv_record(rdr, record)? => {}
+                    None => return Ok(false),
+                }
+                if let Some(ref infer) = self.infer {
+                    infer.apply(record);
+                }
+                record.set_path(self.path.clone());
+                Ok(true)
+            }
+            ReaderInner::Workbook(ref mut workbook) => {
+                if !workbook.read_record(record)? {
+                    return Ok(false);
+                }
+                record.set_path(self.path.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    /// Returns the `row`th (zero-based) unread record of each remaining part without consuming
+    /// any record, where each worksheet of a spreadsheet is a part and csv data is a single part.
+    ///
+    /// Parts that don't have such a record are omitted. Note that peeked csv records are
+    /// buffered until they are read.
+    pub(crate) fn peek(&mut self, row: usize) -> io::Result<Vec<Record>> {
+        match self.inner {
+            ReaderInner::Csv(ref mut rdr) => {
+                while self.peeked.len() <= row {
+                    let mut record = Record::default();
+                    if !read_csv_record(rdr, &mut record)? {
+                        break;
+                    }
+                    self.peeked.push_back(record);
+                }
+                Ok(self.peeked.get(row).cloned().into_iter().collect())
+            }
+            ReaderInner::Workbook(ref mut workbook) => workbook.peek(row),
+        }
+    }
+
+    /// Returns a borrowed iterator over all records.
+    ///
+    /// Each item yielded by this iterator is a `io::Result<Record>`.
+    pub fn records(&mut self) -> RecordsIter<'_, R> {
+        RecordsIter { rdr: self }
+    }
+
+    /// Returns a borrowed iterator deserializing each record into a value of type `D`.
+    ///
+    /// The first record of csv data, as well as of each worksheet, is used as the header whose
+    /// names structs and maps are deserialized by, see [`Record::deserialize`], unless
+    /// [`ReaderBuilder::has_headers`] is turned off.
+    ///
+    /// Each item yielded by this iterator is a `io::Result<D>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::Reader;
+    ///
+    /// let mut rdr = Reader::from_reader("name,qty\napple,3\npear,\n".as_bytes())?;
+    /// let rows: Vec<(String, Option<u32>)> =
+    ///     rdr.deserialize().collect::<Result<_, _>>()?;
+    /// assert_eq!(rows, vec![("apple".into(), Some(3)), ("pear".into(), None)]);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn deserialize<D: DeserializeOwned>(
+        &mut self,
+    ) -> DeserializeRecordsIter<'_, R, D> {
+        DeserializeRecordsIter {
+            rdr: self,
+            record: Record::default(),
+            headers: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Reads a single csv record into the given `record`.
+fn read_csv_record<R: io::Read>(
+    rdr: &mut csv::Reader<LineEnds<R>>,
+    record: &mut Record,
+) -> io::Result<bool> {
+    let byte_record = record.as_byte_record_mut();
+    if !rdr.read_byte_record(byte_record)? {
+        return Ok(false);
+    }
+    let pos = byte_record.position().cloned();
+    let pos = pos.map(|pos| rdr.get_mut().start(pos.byte(), pos.line()));
+    record.set_sheet(None);
+    record.set_row(pos.map(|(_, line)| line));
+    record.set_byte(pos.map(|(byte, _)| byte));
+    Ok(true)
+}
+
+/// A reader that remembers where the line terminators it has read are.
+///
+/// The position csv reports for a record is where the parser stopped after the previous record,
+/// which is before the `\n` of a `\r\n` terminator, or before any empty lines. These positions are
+/// moved past the terminators that follow them.
+struct LineEnds<R> {
+    inner: R,
+    /// The number of bytes read so far.
+    pos: u64,
+    /// The offsets of the `\r` and `\n` bytes read but not yet passed by a record, and whether
+    /// each of them is a `\n`.
+    ends: VecDeque<(u64, bool)>,
+}
+
+impl<R> LineEnds<R> {
+    fn new(inner: R) -> LineEnds<R> {
+        LineEnds { inner, pos: 0, ends: VecDeque::new() }
+    }
+
+    /// Returns the byte offset and the 1-based line number of the record that csv reports at the
+    /// given byte offset and line number.
+    fn start(&mut self, mut byte: u64, mut line: u64) -> (u64, u64) {
+        while self.ends.front().is_some_and(|&(end, _)| end < byte) {
+            self.ends.pop_front();
+        }
+        while let Some(&(end, lf)) = self.ends.front() {
+            if end != byte {
+                break;
+            }
+            self.ends.pop_front();
+            byte += 1;
+            line += lf as u64;
+        }
+        (byte, line)
+    }
+}
+
+impl<R: Read> Read for LineEnds<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for (i, &b) in buf[..n].iter().enumerate() {
+            if b == b'\r' || b == b'\n' {
+                self.ends.push_back((self.pos + i as u64, b == b'\n'));
+            }
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// A borrowed iterator over the records of a [`Reader`].
+///
+/// The `'r` lifetime refers to the lifetime of the `Reader` that is being iterated over.
+pub struct RecordsIter<'r, R> {
+    rdr: &'r mut Reader<R>,
+}
+
+impl<'r, R: Read> Iterator for RecordsIter<'r, R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        let mut record = Record::default();
+        match self.rdr.read_record(&mut record) {
+            Ok(true) => Some(Ok(record)),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// A borrowed iterator deserializing the records of a [`Reader`].
+///
+/// The `'r` lifetime refers to the lifetime of the `Reader` that is being iterated over, and `D`
+/// is the type each record is deserialized into.
+pub struct DeserializeRecordsIter<'r, R, D> {
+    rdr: &'r mut Reader<R>,
+    record: Record,
+    /// The header of the current part, if any.
+    headers: Option<Record>,
+    _marker: PhantomData<D>,
+}
+
+impl<'r, R: Read, D: DeserializeOwned> Iterator
+    for DeserializeRecordsIter<'r, R, D>
+{
+    type Item = io::Result<D>;
+
+    fn next(&mut self) -> Option<io::Result<D>> {
+        loop {
+            match self.rdr.read_record(&mut self.record) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+            if !self.rdr.has_headers {
+                return Some(self.record.deserialize(None));
+            }
+            let headers = match self.headers {
+                Some(ref headers)
+                    if headers.sheet() == self.record.sheet() =>
+                {
+                    headers
+                }
+                // the first record of each worksheet is its header.
+                _ => {
+                    self.headers = Some(self.record.clone());
+                    continue;
+                }
+            };
+            return Some(self.record.deserialize(Some(headers)));
+        }
+    }
+}
+
+impl Reader<File> {
+    /// Creates a new reader with default configuration for the given file path.
+    ///
+    /// # Errors
+    ///
+    /// If there was any problem opening the given file path or reading from it, then this returns
+    /// the corresponding error.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Reader<File>> {
+        ReaderBuilder::new().from_path(path)
+    }
 }
 
 /// Represents the file format.
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
+    /// Comma-separated values, which is also the fallback of unrecognized data.
     Csv,
+    /// Office Open XML workbook.
     Xlsx,
+    /// Excel 97-2003 binary workbook.
     Xls,
+    /// OpenDocument spreadsheet.
     Ods,
+    /// Any other zip archive, such as a docx file.
     Other,
 }
+
+impl Format {
+    /// Detects the file format from the leading bytes of a file.
+    ///
+    /// A zip archive is recognized as a xlsx file if it contains a `[Content_Types].xml` entry or
+    /// any entry under `xl/`, and as an ods file if its `mimetype` entry says so. An OLE2 compound
+    /// file is recognized as a xls file. Anything else is assumed to be csv.
+    ///
+    /// Since entries of a zip archive are only inspected within `buf`, the given bytes should
+    /// cover the leading entries of the archive. Both xlsx and ods writers put the identifying
+    /// entry first, so the first few kilobytes are usually enough. An archive written by a
+    /// streaming writer, whose entry sizes follow their data, may be detected as `Format::Other`
+    /// though, in which case [`ReaderBuilder::from_reader`] reads its central directory instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::reader::Format;
+    ///
+    /// assert_eq!(Format::detect(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1"), Format::Xls);
+    /// assert_eq!(Format::detect(b"a,b,c\n1,2,3\n"), Format::Csv);
+    /// ```
+    pub fn detect(buf: &[u8]) -> Format {
+        if buf.starts_with(CFB_SIGNATURE) {
+            Format::Xls
+        } else if buf.starts_with(ZIP_SIGNATURE) {
+            detect_zip(buf)
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+/// Returns the date system of the xlsx file `buf`, which is the 1904 date system if the
+/// `date1904` attribute of the `workbookPr` element of `xl/workbook.xml` is set.
+///
+/// Any problem reading the workbook is left to be reported when loading it.
+fn xlsx_date_system(buf: &[u8]) -> DateSystem {
+    let mut xml = String::new();
+    let read = zip::ZipArchive::new(Cursor::new(buf)).and_then(|mut zip| {
+        zip.by_name("xl/workbook.xml")?.read_to_string(&mut xml)?;
+        Ok(())
+    });
+    if read.is_err() {
+        return DateSystem::V1900;
+    }
+
+    let props = xml
+        .find("<workbookPr")
+        .map(|start| &xml[start..])
+        .and_then(|tag| tag.find('>').map(|end| &tag[..end]));
+    let date1904 = props.is_some_and(|props| {
+        [
+            "date1904=\"1\"",
+            "date1904=\"true\"",
+            "date1904='1'",
+            "date1904='true'",
+        ]
+        .iter()
+        .any(|attr| props.contains(attr))
+    });
+    if date1904 {
+        DateSystem::V1904
+    } else {
+        DateSystem::V1900
+    }
+}
+
+/// Detects the format of the whole zip archive `buf` by reading its central directory, which
+/// works even if the leading entries can't be walked by [`detect_zip`].
+fn detect_archive(buf: &[u8]) -> Format {
+    let mut zip = match zip::ZipArchive::new(Cursor::new(buf)) {
+        Ok(zip) => zip,
+        Err(_) => return Format::Other,
+    };
+    if zip
+        .file_names()
+        .any(|name| name == "[Content_Types].xml" || name.starts_with("xl/"))
+    {
+        return Format::Xlsx;
+    }
+
+    let mut mime = Vec::new();
+    let read = zip
+        .by_name("mimetype")
+        .and_then(|mut file| Ok(file.read_to_end(&mut mime)?));
+    if read.is_ok() && mime.starts_with(ODS_MIMETYPE) {
+        Format::Ods
+    } else {
+        Format::Other
+    }
+}
+
+/// Detects the format of a zip archive by walking through the local file headers in `buf`.
+fn detect_zip(buf: &[u8]) -> Format {
+    let u16_at = |pos: usize| u16::from_le_bytes([buf[pos], buf[pos + 1]]);
+    let u32_at = |pos: usize| {
+        u32::from_le_bytes([
+            buf[pos],
+            buf[pos + 1],
+            buf[pos + 2],
+            buf[pos + 3],
+        ])
+    };
+
+    let mut pos = 0;
+    while buf.len() >= pos + 30 && buf[pos..].starts_with(ZIP_SIGNATURE) {
+        let flags = u16_at(pos + 6);
+        let compressed_size = u32_at(pos + 18) as usize;
+        let name_len = u16_at(pos + 26) as usize;
+        let extra_len = u16_at(pos + 28) as usize;
+
+        let name = match buf.get(pos + 30..pos + 30 + name_len) {
+            Some(name) => name,
+            None => break,
+        };
+        let data = pos + 30 + name_len + extra_len;
+
+        if name == b"[Content_Types].xml" || name.starts_with(b"xl/") {
+            return Format::Xlsx;
+        }
+        if name == b"mimetype" {
+            // the `mimetype` entry of an ods file must be stored uncompressed.
+            return match buf.get(data..data + compressed_size) {
+                Some(mime) if mime.starts_with(ODS_MIMETYPE) => Format::Ods,
+                _ => Format::Other,
+            };
+        }
+
+        // the sizes are stored after the data if bit 3 is set, so we can't go any further.
+        if flags & 0x08 != 0 {
+            break;
+        }
+        pos = data + compressed_size;
+    }
+
+    Format::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::merger::Format as OutputFormat;
+    use crate::{Field, Writer};
+
+    /// Returns the CRC-32 checksum of `data`.
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc =
+                    (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1));
+            }
+        }
+        !crc
+    }
+
+    /// Rewrites the zip archive `buf` as a streaming writer would, i.e. with the sizes of each
+    /// entry stored in a data descriptor after its data, and with `first` as the first entry.
+    fn streamed(buf: &[u8], first: &str) -> Vec<u8> {
+        let mut zip = zip::ZipArchive::new(Cursor::new(buf)).unwrap();
+        let mut names: Vec<String> =
+            zip.file_names().map(String::from).collect();
+        names.sort_by_key(|name| name != first);
+
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for name in names.iter() {
+            let mut data = Vec::new();
+            zip.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+            let (crc, size) = (crc32(&data), data.len() as u32);
+            let offset = out.len() as u32;
+
+            out.extend_from_slice(b"PK\x03\x04\x14\x00\x08\x00\x00\x00");
+            out.extend_from_slice(&[0; 16]);
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&[0; 2]);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&data);
+            out.extend_from_slice(b"PK\x07\x08");
+            for n in [crc, size, size] {
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+
+            central.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x08\x00");
+            central.extend_from_slice(&[0; 6]);
+            for n in [crc, size, size] {
+                central.extend_from_slice(&n.to_le_bytes());
+            }
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+
+        let (start, len) = (out.len() as u32, central.len() as u32);
+        out.extend_from_slice(&central);
+        out.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+        let count = names.len() as u16;
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&start.to_le_bytes());
+        out.extend_from_slice(&[0; 2]);
+        out
+    }
+
+    #[test]
+    fn detects_xlsx_with_data_descriptors() {
+        let mut buf = Vec::new();
+        let mut wtr =
+            Writer::from_writer(&mut buf, OutputFormat::Xlsx).unwrap();
+        wtr.serialize(("apple", 3)).unwrap();
+        wtr.finish().unwrap();
+
+        let buf = streamed(&buf, "_rels/.rels");
+        assert_eq!(Format::detect(&buf), Format::Other);
+
+        let mut rdr = Reader::from_reader(buf.as_slice()).unwrap();
+        assert_eq!(rdr.format(), Format::Xlsx);
+        let mut record = Record::default();
+        assert!(rdr.read_record(&mut record).unwrap());
+        assert_eq!(record.get(0), Some(crate::Field::Str("apple")));
+        assert_eq!(record.get(1), Some(crate::Field::Float(3.0)));
+    }
+
+    #[test]
+    fn rejects_other_zip_archives() {
+        let mut buf = Vec::new();
+        let mut wtr =
+            Writer::from_writer(&mut buf, OutputFormat::Xlsx).unwrap();
+        wtr.serialize(("apple", 3)).unwrap();
+        wtr.finish().unwrap();
+
+        // drop every entry that identifies a xlsx file.
+        let mut zip = zip::ZipArchive::new(Cursor::new(&buf[..])).unwrap();
+        let mut out = Cursor::new(Vec::new());
+        let mut docx = zip::ZipWriter::new(&mut out);
+        let names: Vec<String> = zip.file_names().map(String::from).collect();
+        for name in names.iter().filter(|name| name.starts_with("_rels/")) {
+            let mut data = Vec::new();
+            zip.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+            docx.start_file(name.as_str(), Default::default()).unwrap();
+            std::io::Write::write_all(&mut docx, &data).unwrap();
+        }
+        docx.finish().unwrap();
+        drop(docx);
+
+        let buf = streamed(out.get_ref(), "_rels/.rels");
+        let err = Reader::from_reader(buf.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// Builds a workbook with the given `workbookPr` attributes and worksheets, each given by its
+    /// name and the content of its `sheetData` element. Cells with the style `1` are date-times.
+    fn workbook(props: &str, sheets: &[(&str, &str)]) -> Vec<u8> {
+        let mut types = String::new();
+        let mut entries = Vec::new();
+        let (mut list, mut rels) = (String::new(), String::new());
+        for (i, (name, data)) in sheets.iter().enumerate() {
+            let n = i + 1;
+            types.push_str(&format!(
+                r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+                n
+            ));
+            list.push_str(&format!(
+                r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#,
+                name, n, n
+            ));
+            rels.push_str(&format!(
+                r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#,
+                n, n
+            ));
+            entries.push((
+                format!("xl/worksheets/sheet{}.xml", n),
+                format!(
+                    r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{}</sheetData></worksheet>"#,
+                    data
+                ),
+            ));
+        }
+        entries.push((
+            "[Content_Types].xml".to_string(),
+            format!(
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>{}</Types>"#,
+                types
+            ),
+        ));
+        entries.push((
+            "_rels/.rels".to_string(),
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_string(),
+        ));
+        entries.push((
+            "xl/workbook.xml".to_string(),
+            format!(
+                r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><workbookPr {}/><sheets>{}</sheets></workbook>"#,
+                props, list
+            ),
+        ));
+        entries.push((
+            "xl/_rels/workbook.xml.rels".to_string(),
+            format!(
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}</Relationships>"#,
+                rels
+            ),
+        ));
+        entries.push((
+            "xl/styles.xml".to_string(),
+            r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><cellXfs count="2"><xf numFmtId="0"/><xf numFmtId="22"/></cellXfs></styleSheet>"#.to_string(),
+        ));
+
+        let mut out = Cursor::new(Vec::new());
+        let mut zip = zip::ZipWriter::new(&mut out);
+        for (name, data) in entries {
+            zip.start_file(name, Default::default()).unwrap();
+            std::io::Write::write_all(&mut zip, data.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        drop(zip);
+        out.into_inner()
+    }
+
+    /// Reads the date-time cells of the first row of the workbook `buf`.
+    fn datetimes(buf: &[u8]) -> Vec<Option<NaiveDateTime>> {
+        let mut rdr = Reader::from_reader(buf).unwrap();
+        let mut record = Record::default();
+        assert!(rdr.read_record(&mut record).unwrap());
+        record.iter().map(|field| field.as_datetime()).collect()
+    }
+
+    fn datetime(s: &str) -> Option<NaiveDateTime> {
+        Some(NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").unwrap())
+    }
+
+    #[test]
+    fn converts_1904_serials() {
+        let row = r#"<row r="1"><c r="A1" s="1"><v>0.5</v></c><c r="B1" s="1"><v>1</v></c><c r="C1" s="1"><v>42735.5</v></c><c r="D1"><v>1</v></c></row>"#;
+        let buf = workbook(r#"date1904="1""#, &[("Sheet1", row)]);
+        assert_eq!(xlsx_date_system(&buf), DateSystem::V1904);
+
+        let mut rdr = Reader::from_reader(buf.as_slice()).unwrap();
+        let mut record = Record::default();
+        assert!(rdr.read_record(&mut record).unwrap());
+        assert_eq!(record.get(1), Some(Field::DateTime(1463.0)));
+        assert_eq!(record.get(3), Some(Field::Float(1.0)));
+        assert_eq!(
+            datetimes(&buf),
+            [
+                // serials less than 1 can't be told apart from time-only values, so the first
+                // day of the 1904 date system reads as a time.
+                datetime("1899-12-31T12:00:00"),
+                datetime("1904-01-02T00:00:00"),
+                datetime("2021-01-01T12:00:00"),
+                None,
+            ]
+        );
+
+        let buf = workbook(r#"date1904="0""#, &[("Sheet1", row)]);
+        assert_eq!(xlsx_date_system(&buf), DateSystem::V1900);
+        assert_eq!(
+            datetimes(&buf),
+            [
+                datetime("1899-12-31T12:00:00"),
+                datetime("1900-01-01T00:00:00"),
+                datetime("2016-12-31T12:00:00"),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_serials_around_the_1900_leap_day() {
+        let row = r#"<row r="1"><c r="A1" s="1"><v>59</v></c><c r="B1" s="1"><v>60</v></c><c r="C1" s="1"><v>61</v></c></row>"#;
+        let buf = workbook("", &[("Sheet1", row)]);
+        assert_eq!(
+            datetimes(&buf),
+            [
+                datetime("1900-02-28T00:00:00"),
+                None,
+                datetime("1900-03-01T00:00:00"),
+            ]
+        );
+
+        // the serials of the 1904 date system never reach the nonexistent leap day.
+        let row = r#"<row r="1"><c r="A1" s="1"><v>59</v></c><c r="B1" s="1"><v>60</v></c></row>"#;
+        let buf = workbook(r#"date1904="true""#, &[("Sheet1", row)]);
+        assert_eq!(
+            datetimes(&buf),
+            [datetime("1904-02-29T00:00:00"), datetime("1904-03-01T00:00:00")]
+        );
+    }
+
+    /// Reads the worksheets of a workbook with three worksheets selected by `sheets`, and returns
+    /// the worksheet name and first cell of each record.
+    fn select(sheets: Sheets) -> io::Result<Vec<(String, String)>> {
+        let cell = |text: &str| {
+            format!(
+                r#"<row r="1"><c r="A1" t="inlineStr"><is><t>{}</t></is></c></row>"#,
+                text
+            )
+        };
+        let buf = workbook(
+            "",
+            &[
+                ("2020-Q4", &cell("a")),
+                ("2021-Q1", &cell("b")),
+                ("2021-Q2", &cell("c")),
+            ],
+        );
+        let mut rdr =
+            ReaderBuilder::new().sheets(sheets).from_reader(&buf[..])?;
+        let mut rows = Vec::new();
+        for record in rdr.records() {
+            let record = record?;
+            let sheet = record.sheet().unwrap().to_string();
+            rows.push((sheet, record.get(0).unwrap().to_display_string()));
+        }
+        Ok(rows)
+    }
+
+    /// Returns the `(sheet, cell)` pair of a row.
+    fn row(sheet: &str, cell: &str) -> (String, String) {
+        (sheet.to_string(), cell.to_string())
+    }
+
+    #[test]
+    fn selects_worksheets() {
+        assert_eq!(select(Sheets::default()).unwrap(), [row("2020-Q4", "a")]);
+        assert_eq!(
+            select(Sheets::Name("2021-Q1".to_string())).unwrap(),
+            [row("2021-Q1", "b")]
+        );
+        assert_eq!(select(Sheets::Index(2)).unwrap(), [row("2021-Q2", "c")]);
+        assert_eq!(
+            select(Sheets::Glob("2021-*".to_string())).unwrap(),
+            [row("2021-Q1", "b"), row("2021-Q2", "c")]
+        );
+        assert_eq!(
+            select(Sheets::All).unwrap(),
+            [row("2020-Q4", "a"), row("2021-Q1", "b"), row("2021-Q2", "c")]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_worksheets() {
+        let err = select(Sheets::Name("2021-Q3".to_string())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(
+            err.to_string().starts_with("worksheet `2021-Q3` not found"),
+            "{}",
+            err
+        );
+
+        let err = select(Sheets::Index(3)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "worksheet index 3 out of range, the spreadsheet has 3 worksheets"
+        );
+
+        let err = select(Sheets::Glob("2022-*".to_string())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "no worksheet matches `2022-*`");
+
+        let err = select(Sheets::Glob("[".to_string())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    /// Returns the line number and byte offset of each record of csv `data`.
+    fn positions(data: &str) -> Vec<(Option<u64>, Option<u64>)> {
+        let mut rdr = Reader::from_reader(data.as_bytes()).unwrap();
+        rdr.records()
+            .map(|record| record.unwrap())
+            .map(|record| (record.row(), record.byte()))
+            .collect()
+    }
+
+    #[test]
+    fn numbers_crlf_lines() {
+        assert_eq!(
+            positions("a,b\r\n1,2\r\n\r\n3,4\r\n"),
+            [(Some(1), Some(0)), (Some(2), Some(5)), (Some(4), Some(12))]
+        );
+        assert_eq!(
+            positions("a,b\n1,2\n\n3,4\n"),
+            [(Some(1), Some(0)), (Some(2), Some(4)), (Some(4), Some(9))]
+        );
+    }
+
+    #[test]
+    fn numbers_lines_after_a_bom() {
+        // the byte order mark is not counted in byte offsets.
+        let expected =
+            [(Some(1), Some(0)), (Some(2), Some(5)), (Some(3), Some(10))];
+        assert_eq!(positions("\u{feff}a,b\r\n1,2\r\n3,4\n"), expected);
+
+        let mut utf16 = vec![0xff, 0xfe];
+        for unit in "a,b\r\n1,2\r\n3,4\n".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        let mut rdr = ReaderBuilder::new()
+            .encoding(Encoding::Auto)
+            .from_reader(utf16.as_slice())
+            .unwrap();
+        let found: Vec<_> = rdr
+            .records()
+            .map(|record| record.unwrap())
+            .map(|record| (record.row(), record.byte()))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn numbers_lines_of_multiline_crlf_records() {
+        assert_eq!(
+            positions("a,b\r\n\"1\r\n2\",3\r\n4,5\r\n"),
+            [(Some(1), Some(0)), (Some(2), Some(5)), (Some(4), Some(15))]
+        );
+    }
+
+    #[test]
+    fn deserializes_headerless_records() {
+        let data = "apple,3\npear,4\n";
+        let mut rdr = Reader::from_reader(data.as_bytes()).unwrap();
+        let rows: Vec<(String, u32)> =
+            rdr.deserialize().map(|row| row.unwrap()).collect();
+        assert_eq!(rows, [("pear".to_string(), 4)]);
+
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(data.as_bytes())
+            .unwrap();
+        let rows: Vec<(String, u32)> =
+            rdr.deserialize().map(|row| row.unwrap()).collect();
+        assert_eq!(rows, [("apple".to_string(), 3), ("pear".to_string(), 4)]);
+    }
+}
@@ -1,12 +1,37 @@
-use std::{fmt, slice};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, io, slice};
 
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use calamine::{CellErrorType, DataType};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use csv::{ByteRecord, ByteRecordIter};
+use serde::Deserialize;
+
+use crate::de::deserialize_record;
+use crate::headers::Headers;
+use crate::infer::is_number;
+use crate::key::{FieldKey, RecordKey};
+use crate::writer::write_field;
+use crate::{DateFormat, DateSystem, Infer};
 
 /// A single xlsx/csv record.
-#[derive(Clone, PartialEq)]
-pub struct Record(RecordInner);
+#[derive(Clone)]
+pub struct Record {
+    inner: RecordInner,
+    /// The name of the worksheet this record was read from, if any.
+    sheet: Option<Arc<str>>,
+    /// The 1-based line (for csv) or row (for spreadsheets) number this record was read from,
+    /// if any.
+    row: Option<u64>,
+    /// The byte offset of the csv record this record was read from, if any.
+    byte: Option<u64>,
+    /// The zero-based index of the merged source this record was read from, if any.
+    source: Option<usize>,
+    /// The path of the file this record was read from, if any.
+    path: Option<Arc<Path>>,
+}
 
 #[derive(Clone, PartialEq)]
 enum RecordInner {
@@ -16,7 +41,7 @@ enum RecordInner {
 
 impl fmt::Debug for Record {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.0 {
+        match self.inner {
             RecordInner::Csv(ref record) => {
                 let mut fields = vec![];
                 for field in record {
@@ -31,40 +56,94 @@ impl fmt::Debug for Record {
     }
 }
 
+impl PartialEq for Record {
+    /// Compares the fields of two records, ignoring where they were read from.
+    fn eq(&self, other: &Record) -> bool {
+        self.inner == other.inner
+    }
+}
+
 impl From<ByteRecord> for Record {
     fn from(record: ByteRecord) -> Record {
-        Record(RecordInner::Csv(record))
+        Record::new(RecordInner::Csv(record))
     }
 }
 
 impl From<Vec<DataType>> for Record {
     fn from(record: Vec<DataType>) -> Self {
-        Record(RecordInner::Xlsx(record))
+        Record::new(RecordInner::Xlsx(record))
     }
 }
 
 impl From<&[DataType]> for Record {
     fn from(record: &[DataType]) -> Self {
-        Record(RecordInner::Xlsx(record.to_vec()))
+        Record::new(RecordInner::Xlsx(record.to_vec()))
+    }
+}
+
+impl<F: Into<FieldBuf>> FromIterator<F> for Record {
+    /// Collects the given fields into a csv record, which is turned into a xlsx record once a
+    /// field that is neither bytes nor a string is collected, see [`Record::push`].
+    fn from_iter<I: IntoIterator<Item = F>>(iter: I) -> Record {
+        let mut record = Record::default();
+        iter.into_iter().for_each(|field| record.push(field));
+        record
+    }
+}
+
+impl Default for Record {
+    /// Returns an empty csv record.
+    fn default() -> Record {
+        Record::new(RecordInner::Csv(ByteRecord::new()))
     }
 }
 
 impl Record {
+    #[inline]
+    fn new(inner: RecordInner) -> Record {
+        Record {
+            inner,
+            sheet: None,
+            row: None,
+            byte: None,
+            source: None,
+            path: None,
+        }
+    }
+
     /// Returns the field at index `i`.
     ///
     /// If no field at index `i` exists, then this returns `None`.
     #[inline]
     pub fn get(&self, i: usize) -> Option<Field<'_>> {
-        match self.0 {
+        match self.inner {
             RecordInner::Csv(ref record) => record.get(i).map(|x| x.into()),
             RecordInner::Xlsx(ref record) => record.get(i).map(|x| x.into()),
         }
     }
 
+    /// Returns the field in the column named `name` of `headers`, see [`Headers::index`] for how
+    /// names are looked up.
+    ///
+    /// If the record has no field in that column, e.g. a short csv row, then this returns
+    /// `Ok(None)`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `name` isn't a column of `headers`, which suggests the closest
+    /// name if any.
+    pub fn get_by_name(
+        &self,
+        headers: &Headers,
+        name: &str,
+    ) -> io::Result<Option<Field<'_>>> {
+        headers.index(name).map(|i| self.get(i))
+    }
+
     /// Returns an iterator over all fields in this record.
     #[inline]
-    pub fn iter(&self) -> RecordIter {
-        match &self.0 {
+    pub fn iter(&self) -> RecordIter<'_> {
+        match &self.inner {
             RecordInner::Csv(record) => {
                 RecordIter(IterInner::CsvRecord(record.iter()))
             }
@@ -86,12 +165,326 @@ impl Record {
         self.len() == 0
     }
 
+    /// Replaces the field at index `i` with `field`.
+    ///
+    /// A csv record is turned into a xlsx record if `field` is neither bytes nor a string, see
+    /// [`Record::push`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn set<F: Into<FieldBuf>>(&mut self, i: usize, field: F) {
+        let len = self.len();
+        assert!(i < len, "index {} out of bounds for {} fields", i, len);
+        self.splice(i, 1, Some(field.into()));
+    }
+
+    /// Appends `field` to the end of this record.
+    ///
+    /// A csv record can only hold bytes, so it is turned into a xlsx record if `field` is neither
+    /// bytes nor a string, in order to keep the type of the value. Any invalid UTF-8 sequence in
+    /// the existing fields is then replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::{Field, FieldBuf, Record};
+    ///
+    /// let mut record: Record = vec!["a", "c"].into_iter().collect();
+    /// record.insert(1, "b");
+    /// record.push(42);
+    /// assert_eq!(record.get(3), Some(Field::Int(42)));
+    /// assert_eq!(record.remove(0), FieldBuf::Str("a".to_string()));
+    /// assert_eq!(record.len(), 3);
+    /// ```
+    pub fn push<F: Into<FieldBuf>>(&mut self, field: F) {
+        let len = self.len();
+        self.splice(len, 0, Some(field.into()));
+    }
+
+    /// Inserts `field` at index `i`, shifting all fields after it to the right.
+    ///
+    /// A csv record is turned into a xlsx record if `field` is neither bytes nor a string, see
+    /// [`Record::push`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is greater than the number of fields.
+    pub fn insert<F: Into<FieldBuf>>(&mut self, i: usize, field: F) {
+        let len = self.len();
+        assert!(i <= len, "index {} out of bounds for {} fields", i, len);
+        self.splice(i, 0, Some(field.into()));
+    }
+
+    /// Removes and returns the field at index `i`, shifting all fields after it to the left.
+    ///
+    /// Fields removed from a csv record are always `FieldBuf::Bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn remove(&mut self, i: usize) -> FieldBuf {
+        let field = match self.get(i) {
+            Some(field) => FieldBuf::from(field),
+            None => {
+                panic!("index {} out of bounds for {} fields", i, self.len())
+            }
+        };
+        self.splice(i, 1, None);
+        field
+    }
+
+    /// Replaces `n` fields starting at index `i` with `field`, if any.
+    fn splice(&mut self, i: usize, n: usize, field: Option<FieldBuf>) {
+        let bytes = match field {
+            Some(FieldBuf::Bytes(_)) | Some(FieldBuf::Str(_)) | None => true,
+            Some(_) => false,
+        };
+        if let (RecordInner::Csv(ref record), false) = (&self.inner, bytes) {
+            let cells = record
+                .iter()
+                .map(|f| Field::Bytes(f).to_data_type())
+                .collect();
+            self.inner = RecordInner::Xlsx(cells);
+        }
+
+        match self.inner {
+            RecordInner::Csv(ref mut record) => {
+                let field = match field {
+                    Some(FieldBuf::Bytes(bytes)) => Some(bytes),
+                    Some(FieldBuf::Str(s)) => Some(s.into_bytes()),
+                    _ => None,
+                };
+                if i == record.len() && n == 0 {
+                    field.iter().for_each(|field| record.push_field(field));
+                    return;
+                }
+
+                let mut fields = ByteRecord::with_capacity(
+                    record.as_slice().len(),
+                    record.len() + 1,
+                );
+                fields.extend(record.iter().take(i));
+                fields.extend(field);
+                fields.extend(record.iter().skip(i + n));
+                fields.set_position(record.position().cloned());
+                *record = fields;
+            }
+            RecordInner::Xlsx(ref mut record) => {
+                record.splice(i..i + n, field.map(DataType::from));
+            }
+        }
+    }
+
+    /// Deserializes this record into a value of type `D`.
+    ///
+    /// If `headers` is given, structs and maps are deserialized by matching their fields against
+    /// the header names, otherwise structs, tuples and sequences are deserialized from the fields
+    /// in order. Empty fields deserialize into `None` for `Option`s.
+    ///
+    /// Spreadsheet cells keep their types, e.g. an integer cell deserializes into any integer type
+    /// it fits, and a float cell without a fractional part does as well. Date-time cells
+    /// deserialize into ISO 8601 date-times such as `2021-01-31T00:00:00`, which map onto
+    /// `chrono::NaiveDateTime`, or into their serial numbers when deserialized as floats. Use
+    /// [`naive_date`](crate::naive_date) and [`naive_time`](crate::naive_time) to deserialize
+    /// them into `NaiveDate` and `NaiveTime`.
+    ///
+    /// # Errors
+    ///
+    /// If the record doesn't match the shape of `D`, an error of kind `InvalidData` wrapping a
+    /// [`DeserializeError`](crate::DeserializeError) is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use xtap_core::{Reader, Record};
+    ///
+    /// let mut rdr = Reader::from_reader("name,qty\napple,3\n".as_bytes())?;
+    /// let mut headers = Record::default();
+    /// let mut record = Record::default();
+    /// rdr.read_record(&mut headers)?;
+    /// rdr.read_record(&mut record)?;
+    ///
+    /// let (name, qty): (&str, u32) = record.deserialize(None)?;
+    /// assert_eq!((name, qty), ("apple", 3));
+    ///
+    /// let row: HashMap<String, String> = record.deserialize(Some(&headers))?;
+    /// assert_eq!(row["qty"], "3");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn deserialize<'de, D: Deserialize<'de>>(
+        &'de self,
+        headers: Option<&'de Record>,
+    ) -> io::Result<D> {
+        deserialize_record(self, headers)
+    }
+
+    /// Returns a key comparing, hashing and ordering this record by the logical values of its
+    /// fields, so that csv and spreadsheet records can be deduplicated, sorted and joined
+    /// together. See [`RecordKey`] for details.
+    ///
+    /// Text is first inferred as a typed value with `infer` if given.
+    pub fn key(&self, infer: Option<&Infer>) -> RecordKey<'_> {
+        RecordKey::new(self, infer)
+    }
+
+    /// Returns the name of the worksheet this record was read from.
+    ///
+    /// This returns `None` if the record wasn't read from a spreadsheet.
+    #[inline]
+    pub fn sheet(&self) -> Option<&str> {
+        self.sheet.as_deref()
+    }
+
+    /// Sets the name of the worksheet this record was read from.
+    #[inline]
+    pub(crate) fn set_sheet(&mut self, sheet: Option<Arc<str>>) {
+        self.sheet = sheet;
+    }
+
+    /// Returns the 1-based line (for csv) or row (for spreadsheets) number this record was read
+    /// from.
+    ///
+    /// The line of a csv record is the line it starts at, which may span multiple lines if a
+    /// quoted field contains newlines. This returns `None` if the record wasn't read by a
+    /// [`Reader`](crate::Reader) or a [`Merger`](crate::Merger).
+    #[inline]
+    pub fn row(&self) -> Option<u64> {
+        self.row
+    }
+
+    /// Sets the 1-based line or row number this record was read from.
+    #[inline]
+    pub(crate) fn set_row(&mut self, row: Option<u64>) {
+        self.row = row;
+    }
+
+    /// Returns the byte offset of the start of the csv record this record was read from.
+    ///
+    /// The offset is counted in the decoded UTF-8 data if the source was transcoded, since the
+    /// offset within the original encoding isn't tracked, and excludes any byte order mark. This
+    /// returns `None` if the record wasn't read from csv data.
+    #[inline]
+    pub fn byte(&self) -> Option<u64> {
+        self.byte
+    }
+
+    /// Sets the byte offset of the csv record this record was read from.
+    #[inline]
+    pub(crate) fn set_byte(&mut self, byte: Option<u64>) {
+        self.byte = byte;
+    }
+
+    /// Returns the zero-based index of the source this record was read from among the sources
+    /// of a [`Merger`](crate::Merger).
+    ///
+    /// This returns `None` if the record wasn't read by a merger.
+    #[inline]
+    pub fn source(&self) -> Option<usize> {
+        self.source
+    }
+
+    /// Sets the zero-based index of the source this record was read from.
+    #[inline]
+    pub(crate) fn set_source(&mut self, source: Option<usize>) {
+        self.source = source;
+    }
+
+    /// Returns the path of the file this record was read from.
+    ///
+    /// This returns `None` if the record was read from a reader rather than a file path.
+    #[inline]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Sets the path of the file this record was read from.
+    #[inline]
+    pub(crate) fn set_path(&mut self, path: Option<Arc<Path>>) {
+        self.path = path;
+    }
+
+    /// Returns the underlying csv record if `self` is a csv record.
+    #[inline]
+    pub(crate) fn as_byte_record(&self) -> Option<&ByteRecord> {
+        match self.inner {
+            RecordInner::Csv(ref record) => Some(record),
+            RecordInner::Xlsx(_) => None,
+        }
+    }
+
+    /// Returns the underlying csv record, turning `self` into an empty csv record first if it is
+    /// a xlsx record.
+    #[inline]
+    pub(crate) fn as_byte_record_mut(&mut self) -> &mut ByteRecord {
+        if let RecordInner::Xlsx(_) = self.inner {
+            self.inner = RecordInner::Csv(ByteRecord::new());
+        }
+        match self.inner {
+            RecordInner::Csv(ref mut record) => record,
+            RecordInner::Xlsx(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the underlying xlsx record, turning `self` into an empty xlsx record first if it is
+    /// a csv record.
+    #[inline]
+    pub(crate) fn as_xlsx_record_mut(&mut self) -> &mut Vec<DataType> {
+        if let RecordInner::Csv(_) = self.inner {
+            self.inner = RecordInner::Xlsx(Vec::new());
+        }
+        match self.inner {
+            RecordInner::Xlsx(ref mut record) => record,
+            RecordInner::Csv(_) => unreachable!(),
+        }
+    }
+
+    /// Trims leading and trailing whitespace from each field. Only ASCII whitespace is trimmed
+    /// unless `unicode` is `true`, in which case e.g. NBSP and U+3000 are trimmed as well.
+    ///
+    /// Only string cells of a xlsx record are trimmed.
+    pub(crate) fn trim(&mut self, unicode: bool) {
+        match self.inner {
+            RecordInner::Csv(ref mut record) => {
+                // `ByteRecord::trim` trims Unicode whitespace in some versions of csv, so ASCII
+                // whitespace is trimmed here explicitly.
+                let mut trimmed: ByteRecord = record
+                    .iter()
+                    .map(|field| {
+                        if unicode {
+                            field.trim()
+                        } else {
+                            field.trim_with(|c| c.is_ascii_whitespace())
+                        }
+                    })
+                    .collect();
+                trimmed.set_position(record.position().cloned());
+                *record = trimmed;
+            }
+            RecordInner::Xlsx(ref mut record) => {
+                for cell in record.iter_mut() {
+                    if let DataType::String(s) = cell {
+                        let trimmed = if unicode {
+                            s.trim()
+                        } else {
+                            s.trim_matches(|c: char| c.is_ascii_whitespace())
+                        };
+                        if trimmed.len() != s.len() {
+                            *s = trimmed.to_string();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Returns the underlying xlsx/csv record stored in this struct.
     ///
     /// If `self` is a csv record, then this will return `(Some(ByteRecord), None)`.
     /// If `self` is a xlsx record, then this will return `(None, Some(Vec<DataType>)`.
     pub fn into_inner(self) -> (Option<ByteRecord>, Option<Vec<DataType>>) {
-        match self.0 {
+        match self.inner {
             RecordInner::Csv(record) => (Some(record), None),
             RecordInner::Xlsx(record) => (None, Some(record)),
         }
@@ -245,3 +638,476 @@ impl<'r> From<&'r DataType> for Field<'r> {
         }
     }
 }
+
+impl<'r> Field<'r> {
+    /// Returns the date-time of a `Field::DateTime`, or `None` for any other field.
+    ///
+    /// Spreadsheets are always read in the 1900 date system, see [`DateSystem::to_datetime`] for
+    /// how serials are converted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use xtap_core::Field;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+    /// assert_eq!(Field::DateTime(44197.5).as_date(), Some(date));
+    /// assert_eq!(
+    ///     Field::DateTime(44197.5).as_datetime(),
+    ///     date.and_hms_opt(12, 0, 0),
+    /// );
+    /// assert_eq!(Field::Float(44197.5).as_datetime(), None);
+    /// ```
+    pub fn as_datetime(&self) -> Option<NaiveDateTime> {
+        match *self {
+            Field::DateTime(serial) => DateSystem::V1900.to_datetime(serial),
+            _ => None,
+        }
+    }
+
+    /// Returns the date part of a `Field::DateTime`, or `None` for any other field.
+    pub fn as_date(&self) -> Option<NaiveDate> {
+        self.as_datetime().map(|datetime| datetime.date())
+    }
+
+    /// Returns the time part of a `Field::DateTime`, discarding its date, or `None` for any other
+    /// field.
+    ///
+    /// This is the whole value of a time-only cell, whose serial is less than `1`.
+    pub fn as_time(&self) -> Option<NaiveTime> {
+        self.as_datetime().map(|datetime| datetime.time())
+    }
+
+    /// Returns the serial of a `Field::DateTime` as a duration of days, rounded to milliseconds,
+    /// or `None` for any other field.
+    ///
+    /// This is the value of an elapsed time cell such as `[h]:mm`, e.g. `1.5` is 36 hours.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match *self {
+            Field::DateTime(serial) if serial.is_finite() => {
+                let ms = (serial * 86_400_000.0).round();
+                Some(Duration::milliseconds(ms as i64))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a key comparing, hashing and ordering this field by its logical value, whose text
+    /// is first inferred as a typed value with `infer` if given. See [`FieldKey`] for details.
+    pub fn key(&self, infer: Option<&Infer>) -> FieldKey<'r> {
+        FieldKey::new(self, infer)
+    }
+
+    /// Returns `true` if this field is `Field::Empty` or empty text.
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            Field::Bytes(bytes) => bytes.is_empty(),
+            Field::Str(s) => s.is_empty(),
+            Field::Empty => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the value of this field as an integer, or `None` if it isn't one.
+    ///
+    /// Text is parsed after trimming whitespace around it, and floats are converted if they have
+    /// no fractional part, so `Int(3)`, `Float(3.0)`, `Str("3")` and `Bytes(b" 3.0 ")` are all
+    /// `3`. Booleans and date-times aren't integers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::Field;
+    ///
+    /// assert_eq!(Field::Float(3.0).as_i64(), Some(3));
+    /// assert_eq!(Field::Bytes(b" 3.0 ").as_i64(), Some(3));
+    /// assert_eq!(Field::Float(3.5).as_i64(), None);
+    /// ```
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Field::Int(int) => Some(int),
+            Field::Float(float) => float_to_i64(float),
+            Field::Bytes(_) | Field::Str(_) => {
+                let text = self.as_str_lossy();
+                let text = text.trim();
+                text.parse().ok().or_else(|| float_to_i64(parse_f64(text)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of this field as a float, or `None` if it isn't a number.
+    ///
+    /// Text is parsed after trimming whitespace around it, in the same way as [`Infer`] does, so
+    /// e.g. `inf` and `NaN` aren't numbers. Booleans and date-times aren't numbers either, see
+    /// [`Field::as_datetime`] for the latter.
+    ///
+    /// [`Infer`]: crate::Infer
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Field::Int(int) => Some(int as f64),
+            Field::Float(float) => Some(float),
+            Field::Bytes(_) | Field::Str(_) => {
+                parse_f64(self.as_str_lossy().trim())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of this field as a boolean, or `None` if it isn't one.
+    ///
+    /// Text is a boolean if it is `true` or `false` in any case after trimming whitespace around
+    /// it, as Excel writes `TRUE` and `FALSE`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Field::Bool(boolean) => Some(boolean),
+            Field::Bytes(_) | Field::Str(_) => {
+                let text = self.as_str_lossy();
+                let text = text.trim();
+                if text.eq_ignore_ascii_case("true") {
+                    Some(true)
+                } else if text.eq_ignore_ascii_case("false") {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the text of this field, borrowed if it is already text.
+    ///
+    /// Invalid UTF-8 sequences in `Field::Bytes` are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    /// Any other field is formatted by [`Field::to_display_string`].
+    pub fn as_str_lossy(&self) -> Cow<'r, str> {
+        match *self {
+            Field::Bytes(bytes) => String::from_utf8_lossy(bytes),
+            Field::Str(s) => Cow::Borrowed(s),
+            _ => Cow::Owned(self.to_display_string()),
+        }
+    }
+
+    /// Returns the textual representation of this field.
+    ///
+    /// Numbers are written in their shortest form, e.g. `Float(3.0)` is `3`, booleans as `true`
+    /// and `false`, errors like `#DIV/0!`, date-times in ISO 8601 like `2021-01-01T12:00:00` and
+    /// `Field::Empty` as an empty string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::Field;
+    ///
+    /// assert_eq!(Field::Float(3.0).to_display_string(), "3");
+    /// assert_eq!(Field::Float(0.1).to_display_string(), "0.1");
+    /// assert_eq!(Field::DateTime(44197.0).to_display_string(), "2021-01-01");
+    /// assert_eq!(Field::Bytes(b"3.0").to_display_string(), "3.0");
+    /// ```
+    pub fn to_display_string(&self) -> String {
+        let date = match *self {
+            Field::DateTime(serial) => DateFormat::Iso8601.format(serial),
+            _ => None,
+        };
+        if let Some(date) = date {
+            return date;
+        }
+
+        let mut buf = Vec::new();
+        // writing into a `Vec` never fails.
+        let _ = write_field(self, &mut buf);
+        match String::from_utf8(buf) {
+            Ok(s) => s,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        }
+    }
+
+    /// Returns a cell holding the value of this field.
+    ///
+    /// Invalid UTF-8 sequences in `Field::Bytes` are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub(crate) fn to_data_type(&self) -> DataType {
+        match *self {
+            Field::Bytes(bytes) => {
+                DataType::String(String::from_utf8_lossy(bytes).into_owned())
+            }
+            Field::Str(s) => DataType::String(s.to_string()),
+            Field::Int(int) => DataType::Int(int),
+            Field::Float(float) => DataType::Float(float),
+            Field::Bool(boolean) => DataType::Bool(boolean),
+            Field::DateTime(serial) => DataType::DateTime(serial),
+            Field::Error(err) => DataType::Error(err.clone()),
+            Field::Empty => DataType::Empty,
+        }
+    }
+}
+
+/// Returns `float` as an integer if it has no fractional part and fits in an `i64`.
+fn float_to_i64(float: f64) -> Option<i64> {
+    let fits = float >= i64::MIN as f64 && float < i64::MAX as f64;
+    (float.fract() == 0.0 && fits).then_some(float as i64)
+}
+
+/// Parses `s` as a float if it looks like a number.
+fn parse_f64(s: &str) -> Option<f64> {
+    is_number(s).then(|| s.parse().ok()).flatten()
+}
+
+/// An owned field, which can be stored into a `Record` or taken out of it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldBuf {
+    Bytes(Vec<u8>),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    DateTime(f64),
+    Error(CellErrorType),
+    Empty,
+}
+
+impl FieldBuf {
+    /// Returns a borrowed view into this field.
+    pub fn as_field(&self) -> Field<'_> {
+        self.into()
+    }
+}
+
+macro_rules! field_buf_from_type {
+    ($variant:ident, $ty:ty) => {
+        impl From<$ty> for FieldBuf {
+            fn from(v: $ty) -> Self {
+                FieldBuf::$variant(v.into())
+            }
+        }
+    };
+}
+
+field_buf_from_type!(Bytes, Vec<u8>);
+field_buf_from_type!(Bytes, &[u8]);
+field_buf_from_type!(Str, String);
+field_buf_from_type!(Str, &str);
+field_buf_from_type!(Int, i64);
+field_buf_from_type!(Float, f64);
+field_buf_from_type!(Bool, bool);
+field_buf_from_type!(Error, CellErrorType);
+
+impl From<()> for FieldBuf {
+    fn from(_: ()) -> Self {
+        FieldBuf::Empty
+    }
+}
+
+impl<T: Into<FieldBuf>> From<Option<T>> for FieldBuf {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(FieldBuf::Empty, Into::into)
+    }
+}
+
+impl<'r> From<Field<'r>> for FieldBuf {
+    fn from(v: Field<'r>) -> FieldBuf {
+        match v {
+            Field::Bytes(bytes) => FieldBuf::Bytes(bytes.to_vec()),
+            Field::Str(s) => FieldBuf::Str(s.to_string()),
+            Field::Int(int) => FieldBuf::Int(int),
+            Field::Float(float) => FieldBuf::Float(float),
+            Field::Bool(boolean) => FieldBuf::Bool(boolean),
+            Field::DateTime(serial) => FieldBuf::DateTime(serial),
+            Field::Error(err) => FieldBuf::Error(err.clone()),
+            Field::Empty => FieldBuf::Empty,
+        }
+    }
+}
+
+impl<'r> From<&'r FieldBuf> for Field<'r> {
+    fn from(v: &'r FieldBuf) -> Field<'r> {
+        match v {
+            FieldBuf::Bytes(bytes) => Field::Bytes(bytes),
+            FieldBuf::Str(s) => Field::Str(s),
+            FieldBuf::Int(int) => Field::Int(*int),
+            FieldBuf::Float(float) => Field::Float(*float),
+            FieldBuf::Bool(boolean) => Field::Bool(*boolean),
+            FieldBuf::DateTime(serial) => Field::DateTime(*serial),
+            FieldBuf::Error(err) => Field::Error(err),
+            FieldBuf::Empty => Field::Empty,
+        }
+    }
+}
+
+impl From<DataType> for FieldBuf {
+    fn from(v: DataType) -> FieldBuf {
+        match v {
+            DataType::Int(int) => FieldBuf::Int(int),
+            DataType::Float(float) => FieldBuf::Float(float),
+            DataType::String(string) => FieldBuf::Str(string),
+            DataType::Bool(boolean) => FieldBuf::Bool(boolean),
+            DataType::Error(err) => FieldBuf::Error(err),
+            DataType::DateTime(serial) => FieldBuf::DateTime(serial),
+            DataType::Empty => FieldBuf::Empty,
+        }
+    }
+}
+
+impl From<FieldBuf> for DataType {
+    /// Invalid UTF-8 sequences in `FieldBuf::Bytes` are replaced with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    fn from(v: FieldBuf) -> DataType {
+        match v {
+            FieldBuf::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(s) => DataType::String(s),
+                Err(err) => DataType::String(
+                    String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                ),
+            },
+            FieldBuf::Str(s) => DataType::String(s),
+            FieldBuf::Int(int) => DataType::Int(int),
+            FieldBuf::Float(float) => DataType::Float(float),
+            FieldBuf::Bool(boolean) => DataType::Bool(boolean),
+            FieldBuf::DateTime(serial) => DataType::DateTime(serial),
+            FieldBuf::Error(err) => DataType::Error(err),
+            FieldBuf::Empty => DataType::Empty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_record(fields: &[&[u8]]) -> Record {
+        let mut record = Record::default();
+        for field in fields {
+            record.as_byte_record_mut().push_field(field);
+        }
+        record
+    }
+
+    fn fields(record: &Record) -> Vec<FieldBuf> {
+        record.iter().map(FieldBuf::from).collect()
+    }
+
+    #[test]
+    fn edits_csv_records_in_place() {
+        let mut record = csv_record(&[b"a", b"c"]);
+        record.set_row(Some(2));
+        record.insert(1, "b");
+        record.insert(3, b"d".to_vec());
+        record.set(0, "A");
+        assert_eq!(record.remove(2), FieldBuf::Bytes(b"c".to_vec()));
+        assert!(record.as_byte_record().is_some());
+        assert_eq!(
+            fields(&record),
+            [
+                FieldBuf::Bytes(b"A".to_vec()),
+                FieldBuf::Bytes(b"b".to_vec()),
+                FieldBuf::Bytes(b"d".to_vec()),
+            ]
+        );
+        assert_eq!(record.row(), Some(2));
+    }
+
+    #[test]
+    fn turns_csv_records_into_xlsx_records_on_typed_fields() {
+        let mut record = csv_record(&[b"a", b"\xffb"]);
+        record.push(42);
+        assert!(record.as_byte_record().is_none());
+        assert_eq!(
+            fields(&record),
+            [
+                FieldBuf::Str("a".to_string()),
+                FieldBuf::Str("\u{fffd}b".to_string()),
+                FieldBuf::Int(42),
+            ]
+        );
+
+        let mut record = csv_record(&[b"a", b"b"]);
+        record.set(1, true);
+        assert_eq!(
+            fields(&record),
+            [FieldBuf::Str("a".to_string()), FieldBuf::Bool(true)]
+        );
+
+        let mut record = csv_record(&[b"a"]);
+        record.insert(0, 1.5);
+        assert_eq!(
+            fields(&record),
+            [FieldBuf::Float(1.5), FieldBuf::Str("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn edits_xlsx_records() {
+        let mut record =
+            Record::from_iter([FieldBuf::Int(1), FieldBuf::Empty]);
+        record.set(1, "b");
+        record.insert(0, FieldBuf::DateTime(0.5));
+        assert_eq!(record.remove(1), FieldBuf::Int(1));
+        record.push(b"c".to_vec());
+        assert_eq!(
+            fields(&record),
+            [
+                FieldBuf::DateTime(0.5),
+                FieldBuf::Str("b".to_string()),
+                FieldBuf::Str("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 out of bounds for 2 fields")]
+    fn panics_on_setting_a_field_out_of_bounds() {
+        csv_record(&[b"a", b"b"]).set(2, "c");
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 out of bounds for 2 fields")]
+    fn panics_on_inserting_a_field_out_of_bounds() {
+        csv_record(&[b"a", b"b"]).insert(3, "c");
+    }
+
+    #[test]
+    #[should_panic(expected = "index 0 out of bounds for 0 fields")]
+    fn panics_on_removing_a_field_out_of_bounds() {
+        Record::default().remove(0);
+    }
+
+    #[test]
+    fn coerces_fields() {
+        assert_eq!(Field::Str(" -12 ").as_i64(), Some(-12));
+        assert_eq!(Field::Bytes(b"1e3").as_i64(), Some(1000));
+        assert_eq!(Field::Float(1e19).as_i64(), None);
+        assert_eq!(Field::Bool(true).as_i64(), None);
+        assert_eq!(Field::DateTime(1.0).as_i64(), None);
+
+        assert_eq!(Field::Int(3).as_f64(), Some(3.0));
+        assert_eq!(Field::Bytes(b" 2.5\t").as_f64(), Some(2.5));
+        assert_eq!(Field::Str("NaN").as_f64(), None);
+        assert_eq!(Field::Str("inf").as_f64(), None);
+        assert_eq!(Field::Empty.as_f64(), None);
+
+        assert_eq!(Field::Str(" TRUE ").as_bool(), Some(true));
+        assert_eq!(Field::Bytes(b"False").as_bool(), Some(false));
+        assert_eq!(Field::Str("yes").as_bool(), None);
+        assert_eq!(Field::Int(1).as_bool(), None);
+
+        assert_eq!(Field::Bytes(b"\xffa").as_str_lossy(), "\u{fffd}a");
+        assert_eq!(Field::Bool(false).as_str_lossy(), "false");
+        assert_eq!(Field::Empty.as_str_lossy(), "");
+        assert_eq!(
+            Field::Error(&CellErrorType::Div0).to_display_string(),
+            "#DIV/0!"
+        );
+        assert_eq!(
+            Field::DateTime(44197.5).to_display_string(),
+            "2021-01-01T12:00:00"
+        );
+    }
+
+    #[test]
+    fn returns_the_time_of_date_times() {
+        let time = NaiveTime::from_hms_opt(12, 0, 0);
+        assert_eq!(Field::DateTime(0.5).as_time(), time);
+        assert_eq!(Field::DateTime(44197.5).as_time(), time);
+        assert_eq!(Field::Float(0.5).as_time(), None);
+    }
+}
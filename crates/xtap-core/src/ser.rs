@@ -0,0 +1,453 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use calamine::DataType;
+use serde::ser::{
+    self, Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeTuple, SerializeTupleStruct, Serializer,
+};
+
+use crate::{Field, Record};
+
+/// An error that occurred while serializing a record.
+#[derive(Debug)]
+struct SerializeError(String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> SerializeError {
+        SerializeError(msg.to_string())
+    }
+}
+
+/// Serializes `value` into `record`, which is turned into a xlsx record so that the values keep
+/// their types.
+///
+/// If `names` is given, the name of each field is pushed into it, which is the field name of a
+/// struct, the key of a map, or `None` for any other value.
+pub(crate) fn serialize_record<S: Serialize + ?Sized>(
+    value: &S,
+    record: &mut Record,
+    names: Option<&mut Vec<Option<String>>>,
+) -> io::Result<()> {
+    let cells = record.as_xlsx_record_mut();
+    cells.clear();
+    let mut ser = SeRecord { cells, names, name: None };
+    value
+        .serialize(&mut ser)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+/// A serializer that flattens a value into the cells of a record.
+struct SeRecord<'a> {
+    cells: &'a mut Vec<DataType>,
+    names: Option<&'a mut Vec<Option<String>>>,
+    /// The name of the next field, if any.
+    name: Option<String>,
+}
+
+impl SeRecord<'_> {
+    fn push(&mut self, cell: DataType) -> Result<(), SerializeError> {
+        self.cells.push(cell);
+        let name = self.name.take();
+        if let Some(ref mut names) = self.names {
+            names.push(name);
+        }
+        Ok(())
+    }
+
+    /// Returns an error if a container of the given kind is the value of a named field.
+    ///
+    /// The fields of such a container wouldn't have names of their own, and a sequence could
+    /// even have a different length in each record.
+    fn check_nested(&self, kind: &str) -> Result<(), SerializeError> {
+        match self.name {
+            Some(ref name) => Err(SerializeError(format!(
+                "cannot serialize {} in the field `{}` of a struct or map",
+                kind, name
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns an error indicating that the given kind of value can't be serialized into a record.
+fn unsupported(kind: &str) -> SerializeError {
+    SerializeError(format!("cannot serialize {} into a record", kind))
+}
+
+impl<'a, 'b> Serializer for &'a mut SeRecord<'b> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), SerializeError>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), SerializeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerializeError> {
+        self.push(DataType::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SerializeError> {
+        // integers that don't fit in a cell are written as strings to keep every digit.
+        match i64::try_from(v) {
+            Ok(v) => self.push(DataType::Int(v)),
+            Err(_) => self.push(DataType::String(v.to_string())),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerializeError> {
+        self.push(DataType::Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerializeError> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), SerializeError> {
+        match i64::try_from(v) {
+            Ok(v) => self.push(DataType::Int(v)),
+            Err(_) => self.push(DataType::String(v.to_string())),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerializeError> {
+        // going through the shortest representation keeps e.g. `0.1f32` from becoming
+        // `0.10000000149011612`.
+        let v = v.to_string().parse().unwrap_or(v as f64);
+        self.push(DataType::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerializeError> {
+        self.push(DataType::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerializeError> {
+        self.push(DataType::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerializeError> {
+        self.push(DataType::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerializeError> {
+        let v = String::from_utf8_lossy(v).into_owned();
+        self.push(DataType::String(v))
+    }
+
+    fn serialize_none(self) -> Result<(), SerializeError> {
+        self.push(DataType::Empty)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerializeError> {
+        self.push(DataType::Empty)
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<(), SerializeError> {
+        self.push(DataType::Empty)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerializeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerializeError> {
+        Err(unsupported("a newtype variant"))
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeSeq, SerializeError> {
+        self.check_nested("a sequence")?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(
+        self,
+        _len: usize,
+    ) -> Result<Self::SerializeTuple, SerializeError> {
+        self.check_nested("a tuple")?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+        self.check_nested("a tuple struct")?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+        Err(unsupported("a tuple variant"))
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeMap, SerializeError> {
+        self.check_nested("a map")?;
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerializeError> {
+        self.check_nested("a struct")?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerializeError> {
+        Err(unsupported("a struct variant"))
+    }
+}
+
+impl SerializeSeq for &mut SeRecord<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTuple for &mut SeRecord<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for &mut SeRecord<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for &mut SeRecord<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(
+        &mut self,
+        key: &T,
+    ) -> Result<(), SerializeError> {
+        let mut cells = Vec::new();
+        key.serialize(&mut SeRecord {
+            cells: &mut cells,
+            names: None,
+            name: None,
+        })?;
+        let cell = match cells.as_slice() {
+            [cell] => cell,
+            _ => return Err(unsupported("a map key of multiple values")),
+        };
+
+        self.name = Some(Field::from(cell).to_display_string());
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeStruct for &mut SeRecord<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.name = Some(key.to_string());
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::merger::Format;
+    use crate::Writer;
+
+    /// A struct whose second field is a sequence.
+    struct Tagged(&'static str, Vec<&'static str>);
+
+    impl Serialize for Tagged {
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            let mut state = ser.serialize_struct("Tagged", 2)?;
+            state.serialize_field("name", self.0)?;
+            state.serialize_field("tags", &self.1)?;
+            state.end()
+        }
+    }
+
+    /// An enum variant holding a single value.
+    struct Newtype(i64);
+
+    impl Serialize for Newtype {
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            ser.serialize_newtype_variant("Value", 0, "Int", &self.0)
+        }
+    }
+
+    /// Serializes `value` into a csv record with headers and returns the output.
+    fn to_csv<S: Serialize>(value: S) -> io::Result<String> {
+        let mut buf = Vec::new();
+        let mut wtr = Writer::from_writer(&mut buf, Format::Csv)?;
+        wtr.serialize(value)?;
+        wtr.finish()?;
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn flattens_top_level_containers() {
+        assert_eq!(to_csv(("a", vec![1, 2])).unwrap(), "a,1,2\n");
+        let map: BTreeMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq!(to_csv(map).unwrap(), "a,b\n1,2\n");
+    }
+
+    #[test]
+    fn rejects_containers_in_fields() {
+        let err = to_csv(Tagged("apple", vec!["red", "sweet"])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut map = BTreeMap::new();
+        map.insert("a", vec![1]);
+        let err = to_csv(map).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // the rows after the header row are checked as well.
+        let mut wtr = Writer::from_writer(Vec::new(), Format::Csv).unwrap();
+        wtr.serialize(("name", "tags")).unwrap();
+        let err = wtr.serialize(Tagged("apple", vec![])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn rejects_newtype_variants() {
+        let err = to_csv(Newtype(3)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result, SeekFrom};
+use std::sync::Arc;
+
+use calamine::{CellErrorType, DataType};
+
+use crate::{Field, Record};
+
+const CSV_RECORD: u8 = 0;
+const XLSX_RECORD: u8 = 1;
+
+const EMPTY: u8 = 0;
+const STRING: u8 = 1;
+const INT: u8 = 2;
+const FLOAT: u8 = 3;
+const BOOL: u8 = 4;
+const DATETIME: u8 = 5;
+const ERROR: u8 = 6;
+
+/// The errors of cells, in the order of their codes.
+const ERRORS: [CellErrorType; 8] = [
+    CellErrorType::Div0,
+    CellErrorType::NA,
+    CellErrorType::Name,
+    CellErrorType::Null,
+    CellErrorType::Num,
+    CellErrorType::Ref,
+    CellErrorType::Value,
+    CellErrorType::GettingData,
+];
+
+/// A temporary file that records are spilled into, so that they can be read again once all
+/// sources have been scanned.
+///
+/// The file is deleted automatically when it is dropped.
+pub(crate) struct Spill {
+    wtr: BufWriter<File>,
+}
+
+impl Spill {
+    /// Creates a new spill file in the temporary directory of the system.
+    pub(crate) fn new() -> Result<Spill> {
+        Ok(Spill { wtr: BufWriter::new(tempfile::tempfile()?) })
+    }
+
+    /// Writes `record` and an arbitrary `tag` into this spill file.
+    pub(crate) fn write(&mut self, record: &Record, tag: u64) -> Result<()> {
+        let wtr = &mut self.wtr;
+        wtr.write_all(&tag.to_le_bytes())?;
+        wtr.write_all(&record.row().map_or(0, |row| row + 1).to_le_bytes())?;
+        wtr.write_all(
+            &record.byte().map_or(0, |byte| byte + 1).to_le_bytes(),
+        )?;
+        let source = record.source().map_or(0, |source| source as u64 + 1);
+        wtr.write_all(&source.to_le_bytes())?;
+        write_bytes(wtr, record.sheet().unwrap_or("").as_bytes())?;
+
+        let kind = match record.as_byte_record() {
+            Some(_) => CSV_RECORD,
+            None => XLSX_RECORD,
+        };
+        wtr.write_all(&[kind])?;
+        wtr.write_all(&(record.len() as u64).to_le_bytes())?;
+        for field in record.iter() {
+            match field {
+                Field::Bytes(bytes) if kind == CSV_RECORD => {
+                    write_bytes(wtr, bytes)?
+                }
+                Field::Bytes(bytes) => {
+                    wtr.write_all(&[STRING])?;
+                    write_bytes(wtr, bytes)?;
+                }
+                Field::Str(s) => {
+                    wtr.write_all(&[STRING])?;
+                    write_bytes(wtr, s.as_bytes())?;
+                }
+                Field::Int(int) => {
+                    wtr.write_all(&[INT])?;
+                    wtr.write_all(&int.to_le_bytes())?;
+                }
+                Field::Float(float) => {
+                    wtr.write_all(&[FLOAT])?;
+                    wtr.write_all(&float.to_le_bytes())?;
+                }
+                Field::Bool(boolean) => {
+                    wtr.write_all(&[BOOL, boolean as u8])?
+                }
+                Field::DateTime(serial) => {
+                    wtr.write_all(&[DATETIME])?;
+                    wtr.write_all(&serial.to_le_bytes())?;
+                }
+                Field::Error(err) => {
+                    let code = ERRORS.iter().position(|e| e == err).unwrap();
+                    wtr.write_all(&[ERROR, code as u8])?;
+                }
+                Field::Empty => wtr.write_all(&[EMPTY])?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes writing and returns a reader over the spilled records.
+    pub(crate) fn into_reader(self) -> Result<SpillReader> {
+        let mut file =
+            self.wtr.into_inner().map_err(|err| err.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(SpillReader { rdr: BufReader::new(file), sheet: None })
+    }
+}
+
+/// A reader over the records of a spill file.
+pub(crate) struct SpillReader {
+    rdr: BufReader<File>,
+    /// The worksheet name of the last record, which is shared by consecutive records.
+    sheet: Option<Arc<str>>,
+}
+
+impl SpillReader {
+    /// Reads the next record into `record`, returning its tag.
+    ///
+    /// Returns `None` if all records have been read.
+    pub(crate) fn read(&mut self, record: &mut Record) -> Result<Option<u64>> {
+        if self.rdr.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let rdr = &mut self.rdr;
+        let tag = read_u64(rdr)?;
+        let row = read_u64(rdr)?.checked_sub(1);
+        let byte = read_u64(rdr)?.checked_sub(1);
+        let source =
+            read_u64(rdr)?.checked_sub(1).map(|source| source as usize);
+        let sheet = read_bytes(rdr)?;
+        if sheet.is_empty() {
+            self.sheet = None;
+        } else if self.sheet.as_deref().map(str::as_bytes) != Some(&sheet) {
+            self.sheet = Some(into_string(sheet)?.into());
+        }
+
+        let kind = read_u8(rdr)?;
+        let len = read_u64(rdr)?;
+        if kind == CSV_RECORD {
+            let fields = record.as_byte_record_mut();
+            fields.clear();
+            for _ in 0..len {
+                fields.push_field(&read_bytes(rdr)?);
+            }
+        } else {
+            let cells = record.as_xlsx_record_mut();
+            cells.clear();
+            for _ in 0..len {
+                let cell = match read_u8(rdr)? {
+                    EMPTY => DataType::Empty,
+                    STRING => DataType::String(into_string(read_bytes(rdr)?)?),
+                    INT => DataType::Int(read_u64(rdr)? as i64),
+                    FLOAT => DataType::Float(f64::from_bits(read_u64(rdr)?)),
+                    BOOL => DataType::Bool(read_u8(rdr)? != 0),
+                    DATETIME => {
+                        DataType::DateTime(f64::from_bits(read_u64(rdr)?))
+                    }
+                    ERROR => match ERRORS.get(read_u8(rdr)? as usize) {
+                        Some(err) => DataType::Error(err.clone()),
+                        None => return Err(corrupted()),
+                    },
+                    _ => return Err(corrupted()),
+                };
+                cells.push(cell);
+            }
+        }
+        record.set_sheet(self.sheet.clone());
+        record.set_row(row);
+        record.set_byte(byte);
+        record.set_source(source);
+        Ok(Some(tag))
+    }
+}
+
+fn write_bytes<W: Write>(wtr: &mut W, bytes: &[u8]) -> Result<()> {
+    wtr.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    wtr.write_all(bytes)
+}
+
+fn read_u8<R: Read>(rdr: &mut R) -> Result<u8> {
+    let mut buf = [0; 1];
+    rdr.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u64<R: Read>(rdr: &mut R) -> Result<u64> {
+    let mut buf = [0; 8];
+    rdr.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes<R: Read>(rdr: &mut R) -> Result<Vec<u8>> {
+    let len = read_u64(rdr)?;
+    let mut buf = Vec::new();
+    rdr.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(corrupted());
+    }
+    Ok(buf)
+}
+
+fn into_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|_| corrupted())
+}
+
+/// Returns an error indicating that the spill file has been corrupted.
+fn corrupted() -> Error {
+    Error::new(ErrorKind::InvalidData, "the spill file has been corrupted")
+}
+
+#[cfg(test)]
+mod tests {
+    use csv::ByteRecord;
+
+    use super::*;
+
+    /// Spills `records` with their indexes as tags, and reads them back.
+    fn round_trip(records: &[Record]) -> Vec<(u64, Record)> {
+        let mut spill = Spill::new().unwrap();
+        for (i, record) in records.iter().enumerate() {
+            spill.write(record, i as u64).unwrap();
+        }
+        let mut rdr = spill.into_reader().unwrap();
+        let mut read = Vec::new();
+        let mut record = Record::default();
+        while let Some(tag) = rdr.read(&mut record).unwrap() {
+            read.push((tag, record.clone()));
+        }
+        read
+    }
+
+    /// Returns the error of reading a spill file with the given contents.
+    fn read_corrupted(contents: &[u8]) -> Error {
+        let mut spill = Spill::new().unwrap();
+        spill.wtr.write_all(contents).unwrap();
+        let mut rdr = spill.into_reader().unwrap();
+        rdr.read(&mut Record::default()).unwrap_err()
+    }
+
+    /// Returns the header of a spilled record up to its worksheet name.
+    fn header(sheet: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for n in [7u64, 1, 1, 1] {
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+        write_bytes(&mut buf, sheet).unwrap();
+        buf
+    }
+
+    #[test]
+    fn round_trips_every_cell_type() {
+        let mut cells = vec![
+            DataType::Empty,
+            DataType::String("apple".into()),
+            DataType::Int(-3),
+            DataType::Int(i64::MAX),
+            DataType::Float(1.5),
+            DataType::Float(f64::INFINITY),
+            DataType::Bool(true),
+            DataType::Bool(false),
+            DataType::DateTime(44227.5),
+        ];
+        cells.extend(ERRORS.iter().cloned().map(DataType::Error));
+
+        let mut record = Record::from(cells);
+        record.set_sheet(Some("Sheet 1".into()));
+        record.set_row(Some(5));
+        record.set_source(Some(2));
+        let read = round_trip(&[record.clone()]);
+
+        assert_eq!(read.len(), 1);
+        let (tag, ref read) = read[0];
+        assert_eq!(tag, 0);
+        assert_eq!(*read, record);
+        assert_eq!(read.sheet(), Some("Sheet 1"));
+        assert_eq!(read.row(), Some(5));
+        assert_eq!(read.byte(), None);
+        assert_eq!(read.source(), Some(2));
+    }
+
+    #[test]
+    fn round_trips_csv_records() {
+        let fields: Vec<&[u8]> = vec![b"a", b"", b"\xff"];
+        let mut first = Record::from(ByteRecord::from(fields));
+        first.set_row(Some(0));
+        first.set_byte(Some(0));
+        first.set_source(Some(0));
+        let mut second = Record::from(ByteRecord::from(vec!["b"]));
+        second.set_row(Some(3));
+        second.set_byte(Some(12));
+        let read = round_trip(&[first.clone(), second.clone(), first.clone()]);
+
+        let tags: Vec<_> = read.iter().map(|(tag, _)| *tag).collect();
+        assert_eq!(tags, [0, 1, 2]);
+        for ((_, read), record) in read.iter().zip([&first, &second, &first]) {
+            assert_eq!(read, record);
+            assert!(read.as_byte_record().is_some());
+            assert_eq!(read.sheet(), None);
+            assert_eq!(read.row(), record.row());
+            assert_eq!(read.byte(), record.byte());
+            assert_eq!(read.source(), record.source());
+        }
+    }
+
+    #[test]
+    fn keeps_sheet_names_of_consecutive_records() {
+        let mut records = Vec::new();
+        for sheet in [Some("a"), Some("a"), None, Some("b")] {
+            let mut record = Record::from(vec![DataType::Int(1)]);
+            record.set_sheet(sheet.map(Arc::from));
+            records.push(record);
+        }
+        let sheets: Vec<_> = round_trip(&records)
+            .iter()
+            .map(|(_, record)| record.sheet().map(String::from))
+            .collect();
+        assert_eq!(
+            sheets,
+            [Some("a".into()), Some("a".into()), None, Some("b".into())]
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_files() {
+        // a record cut short in its header.
+        let err = read_corrupted(&7u64.to_le_bytes()[..4]);
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        // a worksheet name longer than the rest of the file.
+        let mut buf = header(b"");
+        buf.truncate(buf.len() - 8);
+        buf.extend_from_slice(&100u64.to_le_bytes());
+        buf.extend_from_slice(b"Sheet");
+        assert_eq!(read_corrupted(&buf).kind(), ErrorKind::InvalidData);
+
+        // a worksheet name that isn't UTF-8.
+        let mut buf = header(b"\xff");
+        buf.push(XLSX_RECORD);
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(read_corrupted(&buf).kind(), ErrorKind::InvalidData);
+
+        // an unknown cell type.
+        let mut buf = header(b"");
+        buf.push(XLSX_RECORD);
+        buf.extend_from_slice(&1u64.to_le_bytes());
+        buf.push(42);
+        assert_eq!(read_corrupted(&buf).kind(), ErrorKind::InvalidData);
+
+        // an unknown cell error.
+        let mut buf = header(b"");
+        buf.push(XLSX_RECORD);
+        buf.extend_from_slice(&1u64.to_le_bytes());
+        buf.extend_from_slice(&[ERROR, ERRORS.len() as u8]);
+        assert_eq!(read_corrupted(&buf).kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,452 @@
+use std::cmp;
+use std::io::{self, BufRead, Read, Write};
+
+use encoding::types::RawDecoder;
+use encoding::{all, EncoderTrap, EncodingRef};
+
+/// The character encoding of csv data.
+///
+/// Csv data in any encoding other than UTF-8 is transcoded to UTF-8 before parsing. Spreadsheets
+/// always store text in Unicode, so they are never affected by this setting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// Detects the encoding from the byte order mark, falling back to the first of UTF-8,
+    /// UTF-16, GB18030, Big5 and Shift_JIS that the leading bytes are valid in.
+    ///
+    /// Note that GB18030 accepts most Big5 and Shift_JIS data as well, so specify the encoding
+    /// explicitly if Big5 or Shift_JIS data is expected.
+    Auto,
+    /// UTF-8. The data is passed through as is, only a leading byte order mark is removed.
+    #[default]
+    Utf8,
+    /// GBK, the common encoding of simplified Chinese.
+    Gbk,
+    /// GB18030, a superset of GBK.
+    Gb18030,
+    /// Big5, the common encoding of traditional Chinese.
+    Big5,
+    /// Shift_JIS, the common encoding of Japanese.
+    ShiftJis,
+    /// UTF-16 little-endian.
+    Utf16Le,
+    /// UTF-16 big-endian.
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Returns the encoding of the given label, ignoring case.
+    ///
+    /// Besides `auto`, any [WHATWG label](https://encoding.spec.whatwg.org/#names-and-labels) of
+    /// the supported encodings is recognized, e.g. `utf-8`, `gbk`, `gb2312`, `big5`,
+    /// `shift_jis`, `utf-16le` and `utf-16be`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::Encoding;
+    ///
+    /// assert_eq!(Encoding::from_label("GBK"), Some(Encoding::Gbk));
+    /// assert_eq!(Encoding::from_label("Shift_JIS"), Some(Encoding::ShiftJis));
+    /// assert_eq!(Encoding::from_label("koi8-r"), None);
+    /// assert_eq!(Encoding::from_label("euc-kr"), None);
+    /// ```
+    pub fn from_label(label: &str) -> Option<Encoding> {
+        if label.trim().eq_ignore_ascii_case("auto") {
+            return Some(Encoding::Auto);
+        }
+
+        let codec = encoding::label::encoding_from_whatwg_label(label)?;
+        let encoding = match codec.name() {
+            "utf-8" => Encoding::Utf8,
+            "gbk" => Encoding::Gbk,
+            "gb18030" => Encoding::Gb18030,
+            "big5-2003" => Encoding::Big5,
+            "windows-31j" => Encoding::ShiftJis,
+            "utf-16le" => Encoding::Utf16Le,
+            "utf-16be" => Encoding::Utf16Be,
+            _ => return None,
+        };
+        Some(encoding)
+    }
+
+    /// Returns the codec of this encoding, or `None` if no transcoding is needed.
+    fn codec(&self) -> Option<EncodingRef> {
+        match self {
+            Encoding::Auto | Encoding::Utf8 => None,
+            Encoding::Gbk => Some(all::GBK),
+            Encoding::Gb18030 => Some(all::GB18030),
+            Encoding::Big5 => Some(all::BIG5_2003),
+            Encoding::ShiftJis => Some(all::WINDOWS_31J),
+            Encoding::Utf16Le => Some(all::UTF_16LE),
+            Encoding::Utf16Be => Some(all::UTF_16BE),
+        }
+    }
+
+    /// Returns the byte order mark of this encoding, if any.
+    fn bom(&self) -> Option<&'static [u8]> {
+        match self {
+            Encoding::Utf8 => Some(b"\xEF\xBB\xBF"),
+            Encoding::Utf16Le => Some(b"\xFF\xFE"),
+            Encoding::Utf16Be => Some(b"\xFE\xFF"),
+            _ => None,
+        }
+    }
+
+    /// Detects the encoding of data starting with `buf`.
+    fn detect(buf: &[u8]) -> Encoding {
+        let boms = [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be];
+        if let Some(&encoding) =
+            boms.iter().find(|e| buf.starts_with(e.bom().unwrap()))
+        {
+            return encoding;
+        }
+
+        // UTF-16 text consisting of mostly ASCII characters is also valid UTF-8, so check the
+        // distribution of zero bytes first.
+        let zeros = |parity: usize| {
+            buf.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count()
+        };
+        let (even, odd) = (zeros(0), zeros(1));
+        if odd > buf.len() / 4 && even == 0 {
+            return Encoding::Utf16Le;
+        }
+        if even > buf.len() / 4 && odd == 0 {
+            return Encoding::Utf16Be;
+        }
+
+        match std::str::from_utf8(buf) {
+            Ok(_) => return Encoding::Utf8,
+            // the data may be truncated in the middle of a character.
+            Err(err) if err.error_len().is_none() => return Encoding::Utf8,
+            Err(_) => {}
+        }
+
+        let candidates =
+            [Encoding::Gb18030, Encoding::Big5, Encoding::ShiftJis];
+        candidates
+            .iter()
+            .copied()
+            .find(|encoding| {
+                let mut decoder = encoding.codec().unwrap().raw_decoder();
+                let mut output = String::new();
+                decoder.raw_feed(buf, &mut output).1.is_none()
+            })
+            .unwrap_or(Encoding::Utf8)
+    }
+}
+
+/// The character encoding of csv output.
+///
+/// Characters that cannot be represented in the encoding are replaced with `?`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutputEncoding {
+    /// UTF-8 without a byte order mark.
+    #[default]
+    Utf8,
+    /// UTF-8 with a leading byte order mark, which Excel requires to recognize UTF-8 csv files.
+    Utf8Bom,
+    /// GBK, the common encoding of simplified Chinese.
+    Gbk,
+    /// UTF-16 little-endian with a leading byte order mark.
+    Utf16Le,
+}
+
+impl OutputEncoding {
+    /// Returns the codec of this encoding, or `None` if no transcoding is needed.
+    fn codec(&self) -> Option<EncodingRef> {
+        match self {
+            OutputEncoding::Utf8 | OutputEncoding::Utf8Bom => None,
+            OutputEncoding::Gbk => Some(all::GBK),
+            OutputEncoding::Utf16Le => Some(all::UTF_16LE),
+        }
+    }
+
+    /// Returns the byte order mark written before any data, if any.
+    fn bom(&self) -> Option<&'static [u8]> {
+        match self {
+            OutputEncoding::Utf8Bom => Encoding::Utf8.bom(),
+            OutputEncoding::Utf16Le => Encoding::Utf16Le.bom(),
+            OutputEncoding::Utf8 | OutputEncoding::Gbk => None,
+        }
+    }
+}
+
+/// A buffered reader that transcodes data in a given encoding into UTF-8.
+///
+/// Invalid byte sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+pub(crate) struct DecodeReader<R> {
+    rdr: R,
+    /// The decoder in use, or `None` if the data is passed through.
+    decoder: Option<Box<dyn RawDecoder>>,
+    /// The decoded data.
+    buf: String,
+    /// The number of bytes in `buf` that have been consumed.
+    pos: usize,
+    /// Whether the underlying reader has been exhausted.
+    eof: bool,
+}
+
+impl<R: BufRead> DecodeReader<R> {
+    /// Creates a new reader that transcodes the data of `rdr` in the given `encoding`.
+    ///
+    /// A leading byte order mark of the encoding is removed, and `Encoding::Auto` is resolved
+    /// from the first chunk of the data.
+    pub(crate) fn new(
+        mut rdr: R,
+        encoding: Encoding,
+    ) -> io::Result<DecodeReader<R>> {
+        let buf = rdr.fill_buf()?;
+        let encoding = match encoding {
+            Encoding::Auto => Encoding::detect(buf),
+            encoding => encoding,
+        };
+        if let Some(bom) = encoding.bom().filter(|bom| buf.starts_with(bom)) {
+            rdr.consume(bom.len());
+        }
+
+        Ok(DecodeReader {
+            rdr,
+            decoder: encoding.codec().map(|codec| codec.raw_decoder()),
+            buf: String::new(),
+            pos: 0,
+            eof: false,
+        })
+    }
+}
+
+impl<R: BufRead> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = cmp::min(available.len(), buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for DecodeReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let decoder = match self.decoder {
+            Some(ref mut decoder) => decoder,
+            None => return self.rdr.fill_buf(),
+        };
+
+        while self.pos >= self.buf.len() && !self.eof {
+            self.buf.clear();
+            self.pos = 0;
+
+            let input = self.rdr.fill_buf()?;
+            if input.is_empty() {
+                if decoder.raw_finish(&mut self.buf).is_some() {
+                    self.buf.push('\u{FFFD}');
+                }
+                self.eof = true;
+                continue;
+            }
+
+            let consumed = match decoder.raw_feed(input, &mut self.buf) {
+                (_, None) => input.len(),
+                (_, Some(err)) => {
+                    self.buf.push('\u{FFFD}');
+                    // the erroneous bytes may start in the prior input, which has been consumed.
+                    cmp::max(err.upto, 0) as usize
+                }
+            };
+            self.rdr.consume(consumed);
+        }
+
+        Ok(&self.buf.as_bytes()[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self.decoder {
+            Some(_) => self.pos += amt,
+            None => self.rdr.consume(amt),
+        }
+    }
+}
+
+/// A writer that transcodes UTF-8 data into a given encoding.
+///
+/// Invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT CHARACTER` before encoding.
+pub(crate) struct EncodeWriter<W: Write> {
+    wtr: W,
+    /// The codec in use, or `None` if the data is passed through.
+    codec: Option<EncodingRef>,
+    /// The trailing bytes of an incomplete character that haven't been encoded yet.
+    pending: Vec<u8>,
+    /// A reusable buffer of encoded data.
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EncodeWriter<W> {
+    /// Creates a new writer that transcodes data into `wtr` in the given `encoding`.
+    ///
+    /// The byte order mark of the encoding, if any, is written immediately.
+    pub(crate) fn new(
+        mut wtr: W,
+        encoding: OutputEncoding,
+    ) -> io::Result<EncodeWriter<W>> {
+        if let Some(bom) = encoding.bom() {
+            wtr.write_all(bom)?;
+        }
+
+        Ok(EncodeWriter {
+            wtr,
+            codec: encoding.codec(),
+            pending: Vec::new(),
+            buf: Vec::new(),
+        })
+    }
+
+    /// Encodes the first `len` bytes of the pending data into the underlying writer.
+    fn encode_pending(
+        &mut self,
+        codec: EncodingRef,
+        len: usize,
+    ) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.pending[..len]);
+        self.buf.clear();
+        // the replacement trap never fails.
+        let _ = codec.encode_to(&text, EncoderTrap::Replace, &mut self.buf);
+        self.wtr.write_all(&self.buf)?;
+        self.pending.drain(..len);
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let codec = match self.codec {
+            Some(codec) => codec,
+            None => return self.wtr.write(buf),
+        };
+
+        self.pending.extend_from_slice(buf);
+        let len = complete_len(&self.pending);
+        self.encode_pending(codec, len)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(codec) = self.codec {
+            self.encode_pending(codec, self.pending.len())?;
+        }
+        self.wtr.flush()
+    }
+}
+
+/// Returns the length of `buf` without a trailing incomplete UTF-8 character.
+fn complete_len(buf: &[u8]) -> usize {
+    let start = buf.len().saturating_sub(3);
+    for (i, &b) in buf.iter().enumerate().skip(start).rev() {
+        let width = match b {
+            0x00..=0x7F => return buf.len(),
+            0x80..=0xBF => continue,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        return if buf.len() - i < width { i } else { buf.len() };
+    }
+    buf.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Read};
+
+    use super::*;
+
+    /// Decodes `data` in `encoding`, feeding the decoder `chunk` bytes at a time.
+    fn decode(data: &[u8], encoding: Encoding, chunk: usize) -> String {
+        let rdr = BufReader::with_capacity(chunk, data);
+        let mut rdr = DecodeReader::new(rdr, encoding).unwrap();
+        let mut out = String::new();
+        rdr.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn decodes_characters_split_across_buffers() {
+        // `中文,ab` in GBK, whose characters take two bytes each.
+        let gbk = b"\xD6\xD0\xCE\xC4,ab";
+        for chunk in 1..=gbk.len() {
+            assert_eq!(decode(gbk, Encoding::Gbk, chunk), "中文,ab");
+        }
+
+        // `𝄞` is a surrogate pair in UTF-16, which takes four bytes.
+        let utf16 = b"\xFF\xFEa\x00\x34\xD8\x1E\xDDb\x00";
+        for chunk in 2..=utf16.len() {
+            assert_eq!(decode(utf16, Encoding::Utf16Le, chunk), "a𝄞b");
+        }
+
+        // UTF-8 is passed through as is, so split characters are left to the csv parser.
+        let utf8 = "中文".as_bytes();
+        for chunk in 1..=utf8.len() {
+            assert_eq!(decode(utf8, Encoding::Utf8, chunk), "中文");
+        }
+    }
+
+    #[test]
+    fn replaces_invalid_sequences_across_buffers() {
+        // a lead byte followed by an invalid trail byte in the next buffer.
+        let gbk = b"\xD6\xD0\xD6,ab";
+        for chunk in 1..=gbk.len() {
+            assert_eq!(decode(gbk, Encoding::Gbk, chunk), "中\u{FFFD},ab");
+        }
+
+        // a lead byte cut off by the end of the data.
+        let gbk = b"ab\xD6";
+        for chunk in 1..=gbk.len() {
+            assert_eq!(decode(gbk, Encoding::Gbk, chunk), "ab\u{FFFD}");
+        }
+    }
+
+    /// Encodes `data` in `encoding`, writing `chunk` bytes at a time.
+    fn encode(data: &[u8], encoding: OutputEncoding, chunk: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut wtr = EncodeWriter::new(&mut out, encoding).unwrap();
+        for part in data.chunks(chunk) {
+            wtr.write_all(part).unwrap();
+        }
+        wtr.flush().unwrap();
+        drop(wtr);
+        out
+    }
+
+    #[test]
+    fn encodes_characters_split_across_writes() {
+        let text = "中文,𝄞".as_bytes();
+        for chunk in 1..=text.len() {
+            assert_eq!(
+                encode(text, OutputEncoding::Gbk, chunk),
+                b"\xD6\xD0\xCE\xC4,?"
+            );
+            assert_eq!(
+                encode(text, OutputEncoding::Utf16Le, chunk),
+                b"\xFF\xFE\x2D\x4E\x87\x65,\x00\x34\xD8\x1E\xDD"
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_incomplete_characters_on_flush() {
+        // the first two bytes of `中`, which never get completed.
+        let text = b"ab\xE4\xB8";
+        for chunk in 1..=text.len() {
+            assert_eq!(encode(text, OutputEncoding::Gbk, chunk), b"ab?");
+        }
+    }
+
+    #[test]
+    fn finds_trailing_incomplete_characters() {
+        let text = "a中𝄞".as_bytes();
+        assert_eq!(complete_len(text), text.len());
+        assert_eq!(complete_len(&text[..text.len() - 1]), 4);
+        assert_eq!(complete_len(&text[..3]), 1);
+        assert_eq!(complete_len(b""), 0);
+    }
+}
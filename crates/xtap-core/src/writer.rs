@@ -0,0 +1,356 @@
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use csv::Terminator;
+use serde::Serialize;
+
+use crate::merger::{Format, Newline};
+use crate::ser::serialize_record;
+use crate::transcode::EncodeWriter;
+use crate::xlsx::XlsxSink;
+use crate::{DateFormat, Field, OutputEncoding, Record};
+
+/// A destination that merged records are written into.
+pub(crate) trait Sink {
+    /// Writes a single record into this sink.
+    fn write_record(&mut self, record: &Record) -> Result<()>;
+
+    /// Flushes any buffered data and finalizes the output.
+    fn finish(self) -> Result<()>;
+}
+
+/// A sink that writes records as csv.
+pub(crate) struct CsvSink<W: Write> {
+    wtr: csv::Writer<W>,
+    /// A reusable buffer used in formatting non-byte fields.
+    buf: Vec<u8>,
+    /// The format of date-time fields.
+    date_format: DateFormat,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Creates a new csv sink that terminates each record with `newline` and writes date-time
+    /// fields in `date_format`.
+    pub(crate) fn new(
+        wtr: W,
+        newline: Newline,
+        date_format: DateFormat,
+    ) -> CsvSink<W> {
+        let terminator = match newline {
+            Newline::Lf => Terminator::Any(b'\n'),
+            Newline::Crlf => Terminator::CRLF,
+        };
+        let wtr = csv::WriterBuilder::new()
+            .flexible(true)
+            .terminator(terminator)
+            .from_writer(wtr);
+
+        CsvSink { wtr, buf: Vec::new(), date_format }
+    }
+}
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        if let Some(record) = record.as_byte_record() {
+            return Ok(self.wtr.write_byte_record(record)?);
+        }
+
+        for field in record {
+            self.buf.clear();
+            let date = match field {
+                Field::DateTime(serial) => self.date_format.format(serial),
+                _ => None,
+            };
+            match date {
+                Some(date) => self.buf.extend_from_slice(date.as_bytes()),
+                None => write_field(&field, &mut self.buf)?,
+            }
+            self.wtr.write_field(&self.buf)?;
+        }
+        Ok(self.wtr.write_record(None::<&[u8]>)?)
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.wtr.flush()
+    }
+}
+
+/// Writes the textual representation of `field` into `buf`.
+pub(crate) fn write_field(field: &Field<'_>, buf: &mut Vec<u8>) -> Result<()> {
+    match field {
+        Field::Bytes(bytes) => buf.extend_from_slice(bytes),
+        Field::Str(s) => buf.extend_from_slice(s.as_bytes()),
+        Field::Int(int) => write!(buf, "{}", int)?,
+        Field::Float(float) | Field::DateTime(float) => {
+            write!(buf, "{}", float)?
+        }
+        Field::Bool(boolean) => write!(buf, "{}", boolean)?,
+        Field::Error(err) => write!(buf, "{}", err)?,
+        Field::Empty => {}
+    }
+    Ok(())
+}
+
+/// The sink of a [`Writer`].
+enum OutputSink<W: Write> {
+    Csv(Box<CsvSink<EncodeWriter<W>>>),
+    Xlsx(XlsxSink<W>),
+}
+
+impl<W: Write> Sink for OutputSink<W> {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        match self {
+            OutputSink::Csv(sink) => sink.write_record(record),
+            OutputSink::Xlsx(sink) => sink.write_record(record),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            OutputSink::Csv(sink) => sink.finish(),
+            OutputSink::Xlsx(sink) => sink.finish(),
+        }
+    }
+}
+
+/// A typed xlsx/csv writer, which writes records the same way as a merger does.
+///
+/// The output is finished when the writer is dropped, in which case any error is ignored. Call
+/// [`Writer::finish`] to handle such errors, which matters especially for xlsx output since the
+/// workbook is only written out when finished.
+///
+/// To build a custom writer, use [`WriterBuilder`].
+pub struct Writer<W: Write> {
+    /// The sink records are written into, which is `None` once finished.
+    sink: Option<OutputSink<W>>,
+    /// Whether a header row is derived from the first serialized value.
+    has_headers: bool,
+    /// Whether any record has been written.
+    started: bool,
+    /// A reusable record that values are serialized into.
+    record: Record,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer with default configuration that writes into `wtr` in the given
+    /// `format`.
+    ///
+    /// # Errors
+    ///
+    /// See [`WriterBuilder::from_writer`].
+    pub fn from_writer(wtr: W, format: Format) -> Result<Writer<W>> {
+        WriterBuilder::new().from_writer(wtr, format)
+    }
+
+    /// Serializes `value` into a single record and writes it.
+    ///
+    /// Structs, maps, tuples and sequences are flattened into the fields of the record, except
+    /// that the fields of a struct or map can't be containers themselves, and values keep their
+    /// types in xlsx output, e.g. integers are written as number cells. `None` and unit values
+    /// are written as empty fields.
+    ///
+    /// If headers are enabled and nothing has been written yet, a header row is written first,
+    /// which consists of the field names of a struct or the keys of a map. No header row is
+    /// written if any field of the first value doesn't have a name, e.g. if it is a tuple.
+    ///
+    /// # Errors
+    ///
+    /// If `value` can't be serialized into a record, e.g. it is an enum variant with fields, an
+    /// error of kind `InvalidInput` is returned.
+    pub fn serialize<S: Serialize>(&mut self, value: S) -> Result<()> {
+        if self.has_headers && !self.started {
+            let mut names = Vec::new();
+            serialize_record(&value, &mut self.record, Some(&mut names))?;
+            let headers: Option<Record> = names.into_iter().collect();
+            if let Some(headers) = headers {
+                self.sink()?.write_record(&headers)?;
+            }
+        } else {
+            serialize_record(&value, &mut self.record, None)?;
+        }
+
+        self.started = true;
+        let sink = self.sink.as_mut().ok_or_else(finished)?;
+        sink.write_record(&self.record)
+    }
+
+    /// Writes a single record as is.
+    ///
+    /// No header row is derived from any value serialized after a record has been written.
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        self.started = true;
+        self.sink()?.write_record(record)
+    }
+
+    /// Flushes any buffered data and finalizes the output.
+    pub fn finish(mut self) -> Result<()> {
+        match self.sink.take() {
+            Some(sink) => sink.finish(),
+            None => Err(finished()),
+        }
+    }
+
+    fn sink(&mut self) -> Result<&mut OutputSink<W>> {
+        self.sink.as_mut().ok_or_else(finished)
+    }
+}
+
+impl Writer<File> {
+    /// Creates a new writer with default configuration that writes into the given file path.
+    ///
+    /// # Errors
+    ///
+    /// See [`WriterBuilder::from_path`].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
+        WriterBuilder::new().from_path(path)
+    }
+}
+
+impl<W: Write> std::ops::Drop for Writer<W> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            let _ = sink.finish();
+        }
+    }
+}
+
+/// Returns an error indicating that the writer has been finished.
+fn finished() -> Error {
+    Error::other("the writer has been finished")
+}
+
+/// A builder used for configuring a custom writer.
+#[derive(Debug)]
+pub struct WriterBuilder {
+    has_headers: bool,
+    newline: Newline,
+    output_encoding: OutputEncoding,
+    date_format: DateFormat,
+}
+
+impl Default for WriterBuilder {
+    fn default() -> WriterBuilder {
+        WriterBuilder {
+            has_headers: true,
+            newline: Newline::default(),
+            output_encoding: OutputEncoding::default(),
+            date_format: DateFormat::default(),
+        }
+    }
+}
+
+impl WriterBuilder {
+    /// Creates a new builder for configuring a custom writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtap_core::merger::{Format, Newline};
+    /// use xtap_core::WriterBuilder;
+    ///
+    /// let mut out = Vec::new();
+    /// let mut wtr = WriterBuilder::new()
+    ///     .newline(Newline::Lf)
+    ///     .from_writer(&mut out, Format::Csv)?;
+    /// wtr.serialize(("apple", 3))?;
+    /// wtr.serialize(("pear", 0.5))?;
+    /// wtr.finish()?;
+    /// assert_eq!(out, b"apple,3\npear,0.5\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn new() -> WriterBuilder {
+        WriterBuilder::default()
+    }
+
+    /// Whether a header row is derived from the field names of the first serialized value.
+    ///
+    /// This is enabled by default.
+    pub fn has_headers(&mut self, has_headers: bool) -> &mut Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// The newline style used in csv output.
+    ///
+    /// By default, it's `\r\n` on Windows and `\n` on other platforms.
+    pub fn newline(&mut self, newline: Newline) -> &mut Self {
+        self.newline = newline;
+        self
+    }
+
+    /// The encoding of csv output, which is UTF-8 without a BOM by default.
+    ///
+    /// This has no effect on xlsx output, which is always UTF-8.
+    pub fn output_encoding(&mut self, encoding: OutputEncoding) -> &mut Self {
+        self.output_encoding = encoding;
+        self
+    }
+
+    /// The format of date-time fields in csv output, which is the serial number by default.
+    ///
+    /// This has no effect on xlsx output, which keeps date-times as date cells.
+    ///
+    /// # Errors
+    ///
+    /// If `format` is a custom format that can't be written, e.g. `%Y-%Q`, an error of kind
+    /// `InvalidInput` is returned.
+    pub fn date_format(&mut self, format: DateFormat) -> Result<&mut Self> {
+        format.validate()?;
+        self.date_format = format;
+        Ok(self)
+    }
+
+    /// Builds a writer from this configuration that writes into `wtr` in the given `format`.
+    ///
+    /// # Errors
+    ///
+    /// `Format::Bytes` can't be used since there's no source to concatenate, in which case an
+    /// error of kind `InvalidInput` is returned.
+    pub fn from_writer<W: Write>(
+        &self,
+        wtr: W,
+        format: Format,
+    ) -> Result<Writer<W>> {
+        let sink = match format {
+            Format::Csv => {
+                let wtr = EncodeWriter::new(wtr, self.output_encoding)?;
+                let sink =
+                    CsvSink::new(wtr, self.newline, self.date_format.clone());
+                OutputSink::Csv(Box::new(sink))
+            }
+            Format::Xlsx => OutputSink::Xlsx(XlsxSink::new(wtr)?),
+            Format::Bytes => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a writer cannot write in `Format::Bytes`",
+                ))
+            }
+        };
+        Ok(Writer {
+            sink: Some(sink),
+            has_headers: self.has_headers,
+            started: false,
+            record: Record::default(),
+        })
+    }
+
+    /// Builds a writer from this configuration that writes into the given file path.
+    ///
+    /// The output format is `Format::Xlsx` if the given path has a `xlsx` extension, and
+    /// `Format::Csv` otherwise. The file is created if it does not exist, and its contents are
+    /// overwritten if it does.
+    pub fn from_path<P: AsRef<Path>>(&self, path: P) -> Result<Writer<File>> {
+        let format = match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("xlsx") => Format::Xlsx,
+            _ => Format::Csv,
+        };
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        self.from_writer(file, format)
+    }
+}
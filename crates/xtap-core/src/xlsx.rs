@@ -0,0 +1,326 @@
+use std::fmt::Write as _;
+use std::io::prelude::*;
+use std::io::{Cursor, Error, ErrorKind, Result};
+
+use calamine::CellErrorType;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::writer::Sink;
+use crate::{Field, Record};
+
+/// The maximum number of rows allowed in a worksheet.
+const MAX_ROWS: u32 = 1_048_576;
+/// The maximum number of columns allowed in a worksheet.
+const MAX_COLUMNS: usize = 16_384;
+
+/// The style index of date cells, which uses the built-in `m/d/yyyy` number format.
+const DATE_STYLE: u8 = 1;
+/// The style index of date-time cells, which uses the built-in `m/d/yyyy h:mm` number format.
+const DATETIME_STYLE: u8 = 2;
+/// The style index of time-only cells, which uses the built-in `h:mm:ss` number format.
+const TIME_STYLE: u8 = 3;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/></Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Sheet1" sheetId="1" r:id="rId1"/></sheets></workbook>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#;
+
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="1"><font><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="4"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="14" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="22" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="21" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/></cellXfs><cellStyles count="1"><cellStyle name="Normal" xfId="0" builtinId="0"/></cellStyles></styleSheet>"#;
+
+const SHEET_START: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#;
+
+const SHEET_END: &str = "</sheetData></worksheet>";
+
+/// A sink that writes records into the first worksheet of a xlsx workbook.
+///
+/// Fields keep their types, i.e. numbers are written as number cells, booleans as boolean cells
+/// and date-times as number cells with a date format. Csv fields are written as string cells.
+///
+/// Since a xlsx workbook is a zip archive that can only be finalized after all records have been
+/// written, the compressed workbook is kept in memory until `finish` is called.
+pub(crate) struct XlsxSink<W: Write> {
+    wtr: W,
+    zip: ZipWriter<Cursor<Vec<u8>>>,
+    /// The number of rows written so far.
+    rows: u32,
+    /// A reusable buffer used in formatting rows.
+    buf: String,
+}
+
+impl<W: Write> XlsxSink<W> {
+    /// Creates a new xlsx sink that writes the workbook into `wtr` when finished.
+    pub(crate) fn new(wtr: W) -> Result<XlsxSink<W>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated);
+
+        let parts = [
+            ("[Content_Types].xml", CONTENT_TYPES),
+            ("_rels/.rels", ROOT_RELS),
+            ("xl/workbook.xml", WORKBOOK),
+            ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+            ("xl/styles.xml", STYLES),
+        ];
+        for (name, content) in parts.iter() {
+            zip.start_file(*name, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        zip.start_file("xl/worksheets/sheet1.xml", options.large_file(true))?;
+        zip.write_all(SHEET_START.as_bytes())?;
+
+        Ok(XlsxSink { wtr, zip, rows: 0, buf: String::new() })
+    }
+}
+
+impl<W: Write> Sink for XlsxSink<W> {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        if self.rows == MAX_ROWS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a worksheet cannot contain more than {} rows",
+                    MAX_ROWS
+                ),
+            ));
+        }
+        if record.len() > MAX_COLUMNS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a worksheet cannot contain more than {} columns",
+                    MAX_COLUMNS
+                ),
+            ));
+        }
+        self.rows += 1;
+
+        let buf = &mut self.buf;
+        buf.clear();
+        // writing into a `String` never fails.
+        let _ = write!(buf, r#"<row r="{}">"#, self.rows);
+        for (i, field) in record.iter().enumerate() {
+            write_cell(buf, &field, i, self.rows);
+        }
+        buf.push_str("</row>");
+
+        self.zip.write_all(buf.as_bytes())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.zip.write_all(SHEET_END.as_bytes())?;
+        let workbook = self.zip.finish()?.into_inner();
+        self.wtr.write_all(&workbook)?;
+        self.wtr.flush()
+    }
+}
+
+/// Writes a `<c>` element of `field` at the given zero-based column and one-based row into `buf`.
+///
+/// Nothing is written if `field` is empty.
+fn write_cell(buf: &mut String, field: &Field<'_>, col: usize, row: u32) {
+    let start = buf.len();
+    buf.push_str(r#"<c r=""#);
+    push_column_name(buf, col);
+    let _ = write!(buf, r#"{}""#, row);
+
+    match *field {
+        Field::Bytes(bytes) => {
+            push_inline_str(buf, &String::from_utf8_lossy(bytes))
+        }
+        Field::Str(s) => push_inline_str(buf, s),
+        Field::Int(int) => {
+            let _ = write!(buf, "><v>{}</v></c>", int);
+        }
+        Field::Float(float) if float.is_finite() => {
+            let _ = write!(buf, "><v>{}</v></c>", float);
+        }
+        Field::Float(float) => push_inline_str(buf, &float.to_string()),
+        Field::DateTime(serial) if serial.is_finite() => {
+            // serials less than a day have no date part, e.g. `0.25` is 6:00.
+            let style = if serial < 1.0 {
+                TIME_STYLE
+            } else if serial.fract() == 0.0 {
+                DATE_STYLE
+            } else {
+                DATETIME_STYLE
+            };
+            let _ = write!(buf, r#" s="{}"><v>{}</v></c>"#, style, serial);
+        }
+        Field::DateTime(serial) => push_inline_str(buf, &serial.to_string()),
+        Field::Bool(boolean) => {
+            let _ = write!(buf, r#" t="b"><v>{}</v></c>"#, boolean as u8);
+        }
+        // `GettingData` is only displayed while a cell is being computed, so it isn't a valid
+        // error value to store in a cell.
+        Field::Error(CellErrorType::GettingData) => {
+            push_inline_str(buf, &CellErrorType::GettingData.to_string())
+        }
+        Field::Error(err) => {
+            let _ = write!(buf, r#" t="e"><v>{}</v></c>"#, err);
+        }
+        Field::Empty => buf.truncate(start),
+    }
+}
+
+/// Writes the remaining part of an inline string cell into `buf`.
+fn push_inline_str(buf: &mut String, s: &str) {
+    buf.push_str(r#" t="inlineStr"><is><t xml:space="preserve">"#);
+    push_escaped(buf, s);
+    buf.push_str("</t></is></c>");
+}
+
+/// Writes the column name (e.g. `A`, `AB`) of the given zero-based column index into `buf`.
+fn push_column_name(buf: &mut String, col: usize) {
+    let mut name = [0u8; 3];
+    let mut pos = name.len();
+    let mut n = col + 1;
+    while n > 0 {
+        pos -= 1;
+        name[pos] = b'A' + ((n - 1) % 26) as u8;
+        n = (n - 1) / 26;
+    }
+    name[pos..].iter().for_each(|&c| buf.push(c as char));
+}
+
+/// Writes `s` into `buf`, escaping XML special characters and dropping characters that are not
+/// allowed in XML documents.
+fn push_escaped(buf: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\t' | '\n' | '\r' => buf.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => buf.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use super::*;
+    use crate::{FieldBuf, Reader};
+
+    /// Writes `records` into a workbook and returns it.
+    fn workbook(records: &[Record]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut sink = XlsxSink::new(&mut buf).unwrap();
+        for record in records {
+            sink.write_record(record).unwrap();
+        }
+        sink.finish().unwrap();
+        buf
+    }
+
+    /// Returns the content of the entry `name` of the zip archive `buf`.
+    fn entry(buf: &[u8], name: &str) -> String {
+        let mut zip = zip::ZipArchive::new(Cursor::new(buf)).unwrap();
+        let mut content = String::new();
+        zip.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    /// Reads the records of the workbook `buf` back.
+    fn read(buf: &[u8]) -> Vec<Record> {
+        let mut rdr = Reader::from_reader(buf).unwrap();
+        rdr.records().map(|record| record.unwrap()).collect()
+    }
+
+    #[test]
+    fn round_trips_cells() {
+        let record = Record::from_iter([
+            FieldBuf::Bool(true),
+            FieldBuf::Empty,
+            FieldBuf::Str("<a & b>".to_string()),
+            FieldBuf::Int(-3),
+            FieldBuf::Float(2.5),
+            FieldBuf::Bool(false),
+        ]);
+        let buf = workbook(&[record]);
+
+        let sheet = entry(&buf, "xl/worksheets/sheet1.xml");
+        assert!(sheet.contains("&lt;a &amp; b&gt;"));
+        assert!(!sheet.contains(r#"r="B1""#));
+
+        let read = read(&buf);
+        assert_eq!(read.len(), 1);
+        let fields: Vec<_> = read[0].iter().collect();
+        assert_eq!(
+            fields,
+            [
+                Field::Bool(true),
+                Field::Empty,
+                Field::Str("<a & b>"),
+                Field::Float(-3.0),
+                Field::Float(2.5),
+                Field::Bool(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_non_finite_floats_as_strings() {
+        let record = Record::from_iter([
+            FieldBuf::Float(f64::INFINITY),
+            FieldBuf::Float(f64::NAN),
+            FieldBuf::DateTime(f64::NEG_INFINITY),
+        ]);
+        let read = read(&workbook(&[record]));
+        let fields: Vec<_> = read[0].iter().collect();
+        assert_eq!(
+            fields,
+            [Field::Str("inf"), Field::Str("NaN"), Field::Str("-inf")]
+        );
+    }
+
+    #[test]
+    fn styles_dates_date_times_and_times() {
+        let record = Record::from_iter([
+            FieldBuf::DateTime(44197.0),
+            FieldBuf::DateTime(44197.5),
+            FieldBuf::DateTime(0.25),
+        ]);
+        let buf = workbook(&[record]);
+
+        let sheet = entry(&buf, "xl/worksheets/sheet1.xml");
+        assert!(sheet.contains(r#"<c r="A1" s="1"><v>44197</v></c>"#));
+        assert!(sheet.contains(r#"<c r="B1" s="2"><v>44197.5</v></c>"#));
+        assert!(sheet.contains(r#"<c r="C1" s="3"><v>0.25</v></c>"#));
+
+        // the styles are indexes into `cellXfs`, whose number formats are built-in ones.
+        let styles = entry(&buf, "xl/styles.xml");
+        let xfs = styles.split("<cellXfs").nth(1).unwrap();
+        let formats: Vec<_> = xfs
+            .split("<xf ")
+            .skip(1)
+            .map(|xf| xf.split('"').nth(1).unwrap())
+            .collect();
+        assert_eq!(formats, ["0", "14", "22", "21"]);
+
+        let read = read(&buf);
+        let fields: Vec<_> = read[0].iter().collect();
+        assert_eq!(
+            fields,
+            [
+                Field::DateTime(44197.0),
+                Field::DateTime(44197.5),
+                Field::DateTime(0.25),
+            ]
+        );
+    }
+}
@@ -2,5 +2,5 @@ mod lev_distance;
 mod macros;
 mod shell;
 
-pub use lev_distance::{closest, closest_msg};
+pub use lev_distance::{closest, closest_msg, lev_distance};
 pub use shell::{ColorChoice, Shell, TtyWidth};